@@ -1,4 +1,4 @@
-use leptos::prelude::*;
+use leptos::{either::Either, prelude::*};
 
 #[component]
 pub fn ActiveIcon<S, F>(name: S, active: F) -> impl IntoView
@@ -14,6 +14,7 @@ pub enum IconSize {
     Small,
     #[default]
     Medium,
+    Large,
 }
 
 #[component]
@@ -31,13 +32,36 @@ where
 }
 
 #[component]
-pub fn BaseIcon<S, FSrc, FSize, FActive>(src: FSrc, size: FSize, active: FActive) -> impl IntoView
+pub fn BaseIcon<S, FSrc, FSize, FActive>(
+    src: FSrc,
+    size: FSize,
+    active: FActive,
+    /// remote image (e.g. a thumbnail url) shown instead of the static icon
+    #[prop(default = None)]
+    remote: Option<String>,
+) -> impl IntoView
 where
     S: ToString,
     FSrc: Fn() -> S + 'static + Send,
     FSize: Fn() -> IconSize + 'static + Send,
     FActive: Fn() -> bool + 'static + Send,
 {
+    let width = move || match size() {
+        IconSize::Small => 30,
+        IconSize::Medium => 65,
+        IconSize::Large => 200,
+    };
+    if let Some(remote) = remote {
+        return Either::Left(view! {
+            <img
+                class="m-1 p-1 object-contain aspect-square"
+                class:opacity-40=move || !active()
+                src=remote
+                width=width
+                loading="lazy"
+            />
+        });
+    }
     let src = move || {
         let name = format!("{}.png", src().to_string());
         let name = if active() {
@@ -47,9 +71,5 @@ where
         };
         format!("public/{name}")
     };
-    let width = move || match size() {
-        IconSize::Small => 30,
-        IconSize::Medium => 65,
-    };
-    view! { <img class="m-1 p-1" src=src width=width /> }
+    Either::Right(view! { <img class="m-1 p-1" src=src width=width /> })
 }
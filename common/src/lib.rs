@@ -1,7 +1,10 @@
 use leptos::prelude::document;
 pub use reactive_stores::Store;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, path::PathBuf};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 use web_sys::wasm_bindgen::JsCast;
 
 pub const LS_PATH: &str = "/ls";
@@ -12,6 +15,36 @@ pub const CP_PATH: &str = "/cp";
 pub const MV_PATH: &str = "/mv";
 pub const RM_PATH: &str = "/rm";
 pub const DISKS_PATH: &str = "/disks";
+pub const THUMBNAIL_PATH: &str = "/thumbnail";
+pub const POSTER_PATH: &str = "/poster";
+pub const PREVIEW_PATH: &str = "/preview";
+pub const HLS_PATH: &str = "/hls";
+pub const CONVERT_PATH: &str = "/convert";
+pub const PROBE_PATH: &str = "/probe";
+pub const MUSIC_PATH: &str = "/music";
+pub const COVER_PATH: &str = "/cover";
+pub const SUBTITLES_PATH: &str = "/subtitles";
+pub const SUBTITLE_PATH: &str = "/subtitle";
+pub const PROCESSES_PATH: &str = "/processes";
+pub const PARTITIONS_PATH: &str = "/partitions";
+pub const MOUNT_PATH: &str = "/mount";
+pub const UNMOUNT_PATH: &str = "/unmount";
+pub const EJECT_PATH: &str = "/eject";
+pub const EVENTS_PATH: &str = "/events";
+pub const SHARES_PATH: &str = "/shares";
+pub const SETTINGS_PATH: &str = "/settings";
+pub const ARCHIVE_PATH: &str = "/archive";
+pub const EXTRACT_PATH: &str = "/extract";
+pub const JOBS_PATH: &str = "/jobs";
+pub const CHECKSUM_PATH: &str = "/checksum";
+pub const CHECKSUMS_PATH: &str = "/checksums";
+pub const VERIFY_PATH: &str = "/verify";
+pub const LINKS_PATH: &str = "/links";
+pub const LINK_RM_PATH: &str = "/links/rm";
+/// share links are served under `/s/{token}`, with the site and the routes below
+pub const LINK_PREFIX: &str = "/s";
+pub const LINK_INFO_PATH: &str = "/link";
+pub const UNLOCK_PATH: &str = "/unlock";
 
 #[derive(Default, Clone, Debug)]
 pub enum SelectedState {
@@ -21,11 +54,424 @@ pub enum SelectedState {
     None,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ConvertProfile {
+    /// copy the streams into an mp4 container without re-encoding
+    Remux,
+    /// h264/aac mp4 at the original resolution
+    #[default]
+    Mp4,
+    /// h264/aac mp4 scaled down to at most 720 lines
+    H264Hd720,
+    Mp3,
+    Opus,
+}
+
+impl ConvertProfile {
+    pub const ALL: [Self; 5] = [
+        Self::Remux,
+        Self::Mp4,
+        Self::H264Hd720,
+        Self::Mp3,
+        Self::Opus,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Remux | Self::Mp4 | Self::H264Hd720 => "mp4",
+            Self::Mp3 => "mp3",
+            Self::Opus => "opus",
+        }
+    }
+}
+
+impl Display for ConvertProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let result = match self {
+            Self::Remux => "copy remux",
+            Self::Mp4 => "h264 mp4",
+            Self::H264Hd720 => "h264 720p",
+            Self::Mp3 => "mp3 audio",
+            Self::Opus => "opus audio",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+/// how a selection is packed, to be downloaded at once or kept on the server
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ArchiveFormat {
+    /// deflated, except what is compressed already like media
+    #[default]
+    Zip,
+    Tar,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    pub const ALL: [Self; 3] = [Self::Zip, Self::Tar, Self::TarZst];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::Tar => "tar",
+            Self::TarZst => "tar.zst",
+        }
+    }
+}
+
+/// an archive of `targets` to write in `to`, as `name` with the format's extension
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NewArchive {
+    pub targets: Vec<PathBuf>,
+    pub to: PathBuf,
+    pub name: String,
+    pub format: ArchiveFormat,
+    pub naming: OutputNaming,
+}
+
+/// archives the server can unpack, told apart by their extension
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveKind {
+    pub fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        [
+            (".zip", Self::Zip),
+            (".tar", Self::Tar),
+            (".tar.gz", Self::TarGz),
+            (".tgz", Self::TarGz),
+            (".tar.zst", Self::TarZst),
+            (".tzst", Self::TarZst),
+        ]
+        .into_iter()
+        .find(|(x, _)| name.ends_with(x))
+        .map(|(_, x)| x)
+    }
+}
+
+/// unpacks `archives` into the `to` directory
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Extraction {
+    pub archives: Vec<PathBuf>,
+    pub to: PathBuf,
+    /// for files the archives hold that `to` has already
+    pub naming: OutputNaming,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum JobState {
+    Running,
+    Done,
+    Failed(String),
+}
+
+/// work the server does in the background, like extracting archives
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JobInfo {
+    pub id: u64,
+    pub name: String,
+    /// progress in units only the job knows, bytes most of the time
+    pub done: u64,
+    pub total: u64,
+    pub state: JobState,
+}
+
+impl JobInfo {
+    pub fn percent(&self) -> u64 {
+        match self.state {
+            JobState::Done => 100,
+            _ => (self.done * 100)
+                .checked_div(self.total)
+                .unwrap_or(0)
+                .min(100),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    /// much faster, what copies are verified with
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [Self; 2] = [Self::Sha256, Self::Blake3];
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let result = match self {
+            Self::Sha256 => "SHA-256",
+            Self::Blake3 => "BLAKE3",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Checksums {
+    pub targets: Vec<PathBuf>,
+    pub algorithm: HashAlgorithm,
+}
+
+/// a checksum the server computed, in lowercase hex
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileChecksum {
+    pub path: PathBuf,
+    pub algorithm: HashAlgorithm,
+    pub hex: String,
+}
+
+/// what to do when an output file name is already taken
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum OutputNaming {
+    /// fail the conversion, or the extraction
+    #[default]
+    Keep,
+    /// pick `name (1).ext`, `name (2).ext`, ...
+    Number,
+    Overwrite,
+}
+
+impl OutputNaming {
+    pub const ALL: [Self; 3] = [Self::Keep, Self::Number, Self::Overwrite];
+}
+
+impl Display for OutputNaming {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let result = match self {
+            Self::Keep => "fail if taken",
+            Self::Number => "number the copy",
+            Self::Overwrite => "overwrite",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Conversion {
+    pub targets: Vec<PathBuf>,
+    pub profile: ConvertProfile,
+    pub naming: OutputNaming,
+    pub keep_source: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MediaStream {
+    pub kind: StreamKind,
+    pub codec: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub channels: Option<u32>,
+    pub sample_rate: Option<u32>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+}
+
+/// the usual ID3, Vorbis comment and MP4 atom tags
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AudioTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track: Option<u32>,
+    pub disc: Option<u32>,
+}
+
+/// what ffprobe knows about a video or audio file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MediaInfo {
+    pub format: String,
+    /// seconds
+    pub duration: Option<f64>,
+    /// bits per second
+    pub bit_rate: Option<u64>,
+    pub streams: Vec<MediaStream>,
+    pub tags: AudioTags,
+}
+
+impl MediaInfo {
+    pub fn video(&self) -> Option<&MediaStream> {
+        self.streams.iter().find(|x| x.kind == StreamKind::Video)
+    }
+
+    pub fn audio(&self) -> impl Iterator<Item = &MediaStream> {
+        self.streams.iter().filter(|x| x.kind == StreamKind::Audio)
+    }
+
+    /// whether every major browser plays the file as is
+    pub fn browser_friendly(&self) -> bool {
+        let container = ["mp4", "webm"]
+            .iter()
+            .any(|x| self.format.split(',').any(|f| f == *x));
+        let video = self
+            .video()
+            .is_none_or(|x| matches!(x.codec.as_str(), "h264" | "vp8" | "vp9" | "av1"));
+        let audio = self
+            .audio()
+            .all(|x| matches!(x.codec.as_str(), "aac" | "mp3" | "opus" | "vorbis" | "flac"));
+        container && video && audio
+    }
+
+    pub fn duration_text(&self) -> Option<String> {
+        self.duration.map(duration_text)
+    }
+}
+
+/// `h:mm:ss`, or `m:ss` under an hour
+pub fn duration_text(seconds: f64) -> String {
+    let total = seconds as u64;
+    let (h, m, s) = (total / 3600, total / 60 % 60, total % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+/// pushed to every connected client over server sent events
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum ServerEvent {
+    /// `at` is relative to the shared root, `None` when the drive was not mounted there
+    DriveAdded {
+        name: String,
+        at: Option<PathBuf>,
+    },
+    DriveRemoved {
+        name: String,
+    },
+}
+
+/// load of the external process limiter of the server
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProcessStatus {
+    pub running: usize,
+    /// waiting for one of the running ones to finish
+    pub pending: usize,
+    pub limit: usize,
+}
+
+/// how the server was started, as far as the site cares
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Settings {
+    /// nothing can be uploaded, moved, deleted or converted
+    pub read_only: bool,
+}
+
+/// a directory the server shows under its own name at the top of the tree
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ShareInfo {
+    pub name: String,
+    pub read_only: bool,
+}
+
+/// what sharing a file or directory by link takes
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NewLink {
+    pub path: PathBuf,
+    /// seconds from now
+    pub expires_in: u64,
+    pub password: Option<String>,
+    pub max_downloads: Option<u32>,
+}
+
+/// a share link, as whoever made it sees it
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LinkInfo {
+    pub token: String,
+    pub path: PathBuf,
+    /// unix seconds
+    pub expires: u64,
+    pub password: bool,
+    pub max_downloads: Option<u32>,
+    pub downloads: u32,
+}
+
+/// what anyone holding a share link learns about it
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PublicLink {
+    pub name: String,
+    pub dir: bool,
+    /// a password has to be given first
+    pub locked: bool,
+    /// unix seconds
+    pub expires: u64,
+}
+
+/// a subtitle track of a video, served as WebVTT from [`SUBTITLE_PATH`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Subtitle {
+    pub label: String,
+    pub language: Option<String>,
+    /// the sidecar file, or the video itself for embedded tracks
+    pub path: PathBuf,
+    /// index among the subtitle streams of an embedded track
+    pub stream: Option<usize>,
+}
+
+/// an audio file of the music library
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Track {
+    pub path: PathBuf,
+    pub tags: AudioTags,
+    pub duration: Option<f64>,
+}
+
+impl Track {
+    pub fn artist(&self) -> &str {
+        self.tags
+            .album_artist
+            .as_deref()
+            .or(self.tags.artist.as_deref())
+            .unwrap_or("unknown artist")
+    }
+
+    pub fn album(&self) -> &str {
+        self.tags.album.as_deref().unwrap_or("unknown album")
+    }
+
+    pub fn title(&self) -> String {
+        self.tags.title.clone().unwrap_or_else(|| {
+            self.path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default()
+                .to_string()
+        })
+    }
+
+    pub fn unit(&self) -> Unit {
+        Unit {
+            path: self.path.clone(),
+            kind: UnitKind::Audio,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum UnitKind {
     Dirctory,
     Video,
     Audio,
+    Image,
     File,
 }
 
@@ -36,6 +482,7 @@ impl Display for UnitKind {
             UnitKind::File => "file",
             UnitKind::Video => "video",
             UnitKind::Audio => "audio",
+            UnitKind::Image => "image",
         };
         write!(f, "{}", result)
     }
@@ -71,6 +518,21 @@ pub const AUDIO_X: [&str; 20] = [
     "wma", "au", "gsm", "amr", "ra", "mmf", "cda",
 ];
 
+pub const IMAGE_X: [&str; 12] = [
+    "jpg", "jpeg", "jfif", "png", "webp", "gif", "bmp", "svg", "avif", "ico", "tif", "tiff",
+];
+
+/// images the server decodes to thumbnail them, browsers show the others as they are
+pub const THUMBNAIL_X: [&str; 10] = [
+    "jpg", "jpeg", "jfif", "png", "webp", "gif", "bmp", "ico", "tif", "tiff",
+];
+
+pub fn thumbnailable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| THUMBNAIL_X.contains(&x.to_lowercase().as_str()))
+}
+
 pub trait SortUnits {
     fn sort_units(&mut self);
 }
@@ -92,10 +554,14 @@ impl Retype for Vec<Unit> {
                 return;
             }
             if let Some(x) = unit.path.extension().and_then(|x| x.to_str()) {
+                let x = x.to_lowercase();
+                let x = x.as_str();
                 if VIDEO_X.contains(&x) {
                     unit.kind = UnitKind::Video;
                 } else if AUDIO_X.contains(&x) {
                     unit.kind = UnitKind::Audio;
+                } else if IMAGE_X.contains(&x) {
+                    unit.kind = UnitKind::Image;
                 }
             };
         });
@@ -171,12 +637,34 @@ impl Selected {
 pub struct GlobalState {
     select: Selected,
     media_play: Option<Unit>,
+    image_view: Option<Unit>,
     units_refetch_tick: bool,
+    /// toggled after starting a job, the jobs panel keeps polling while any runs
+    jobs_refetch_tick: bool,
+    /// hash what is pasted as a copy, and its source, to tell they match
+    verify_copies: bool,
     mkdir_state: Option<String>,
     password: bool,
+    gallery: bool,
+    music: bool,
+    /// what the media player steps through instead of the listing, when it holds the playing unit
+    queue: Vec<Unit>,
+    /// the named shares at the top of the tree
+    shares: Vec<ShareInfo>,
+    settings: Settings,
 }
 
 impl GlobalState {
+    /// the share a top level unit stands for
+    pub fn share_of(&self, unit: &Unit) -> Option<&ShareInfo> {
+        let mut components = unit.path.components();
+        let name = components.next()?.as_os_str();
+        if components.next().is_some() {
+            return None;
+        }
+        self.shares.iter().find(|x| name == x.name.as_str())
+    }
+
     pub fn new_store() -> Store<Self> {
         Store::new(Self::default())
     }
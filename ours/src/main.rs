@@ -34,6 +34,11 @@ struct State {
     ip: IpAddr,
     port: u16,
     target_path: Option<PathBuf>,
+    read_only: bool,
+    /// of the certificate, when serving https
+    fingerprint: Option<String>,
+    /// why https could not be turned on
+    tls_error: Option<String>,
     url: Data,
     working_process: Option<Arc<JoinHandle<()>>>,
 }
@@ -45,12 +50,27 @@ impl State {
             ip,
             port,
             target_path: target_path.clone(),
+            read_only: false,
+            fingerprint: None,
+            tls_error: None,
             url: Data::new(format!("http://{ip}:{port}").into_bytes()).unwrap(),
             working_process: None,
         }
     }
     fn url(&self) -> String {
-        format!("http://{}:{}", self.ip, self.port)
+        let scheme = if self.fingerprint.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        format!("{scheme}://{}:{}", self.ip, self.port)
+    }
+    /// self signed, kept so phones only have to trust it once
+    fn tls(&self) -> server::Tls {
+        server::Tls::SelfSigned {
+            dir: home_dir().unwrap_or_default().join(".config/ours/tls"),
+            names: vec![self.ip.to_string(), String::from("localhost")],
+        }
     }
 }
 
@@ -60,9 +80,11 @@ enum Message {
     Stop(Arc<JoinHandle<()>>),
     PickTarget,
     TargetPicked(Option<PathBuf>),
+    ToggleReadOnly,
+    ToggleHttps,
 }
 
-async fn serve(root: PathBuf, port: u16) {
+async fn serve(root: PathBuf, port: u16, read_only: bool, tls: Option<server::Tls>) {
     let mut site = args()
         .next()
         .and_then(|x| x.parse::<PathBuf>().ok())
@@ -71,11 +93,13 @@ async fn serve(root: PathBuf, port: u16) {
     site.pop();
     site.push("site");
 
-    server::Server::new(site, root)
+    let mut server = server::Server::new(site, root)
         .port(port)
-        .serve()
-        .await
-        .unwrap();
+        .read_only(read_only);
+    if let Some(tls) = tls {
+        server = server.tls(tls);
+    }
+    server.serve().await.unwrap();
 }
 
 async fn which_target() -> Option<PathBuf> {
@@ -89,7 +113,12 @@ impl State {
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::Launch => {
-                let i = tokio::spawn(serve(self.target_path.clone().unwrap(), self.port));
+                let i = tokio::spawn(serve(
+                    self.target_path.clone().unwrap(),
+                    self.port,
+                    self.read_only,
+                    self.fingerprint.is_some().then(|| self.tls()),
+                ));
                 self.working_process = Some(i.into());
                 Task::none()
             }
@@ -105,14 +134,46 @@ impl State {
                 }
                 Task::none()
             }
+            Message::ToggleReadOnly => {
+                self.read_only = !self.read_only;
+                Task::none()
+            }
+            Message::ToggleHttps => {
+                self.tls_error = None;
+                self.fingerprint = match self.fingerprint {
+                    Some(_) => None,
+                    None => match self.tls().load().and_then(|x| x.fingerprint()) {
+                        Ok(fingerprint) => Some(fingerprint),
+                        Err(err) => {
+                            self.tls_error = Some(format!("no certificate : {err}"));
+                            None
+                        }
+                    },
+                };
+                self.url = Data::new(self.url().into_bytes()).unwrap();
+                Task::none()
+            }
         }
     }
 
-    fn view(&self) -> Column<Message> {
+    fn view(&self) -> Column<'_, Message> {
         let serve = self.serve_button();
         let tp = self.target_pick();
+        let ro = self.read_only_button();
+        let https = self.https_button();
         let us = self.url_section();
-        column![serve, tp, us,]
+        let tls_error = self.tls_error.as_ref().map(|x| {
+            text(x)
+                .size(18)
+                .width(600)
+                .align_x(Center)
+                .color(Color::from_rgb(1., 0.3, 0.3))
+        });
+        let options = column![row![ro, https].spacing(20.)]
+            .push_maybe(tls_error)
+            .spacing(10)
+            .align_x(Center);
+        column![serve, tp, options, us,]
             .spacing(30)
             .padding(20)
             .align_x(Center)
@@ -122,7 +183,7 @@ impl State {
         self.working_process.is_some()
     }
 
-    fn target_pick(&self) -> Row<Message> {
+    fn target_pick(&self) -> Row<'_, Message> {
         let my_text = |x: String| text(x).size(60).align_x(Center).center();
         let target = my_text(
             self.target_path
@@ -137,15 +198,24 @@ impl State {
         row![target, or, pick].align_y(Center).spacing(20.)
     }
 
-    fn url_section(&self) -> Column<Message> {
+    fn url_section(&self) -> Column<'_, Message> {
         let my_text = |x: String| text(x).size(60).align_x(Center).center();
         let at = my_text(String::from("at"));
         let url = my_text(self.url());
         let qr = qr_code(&self.url).cell_size(13);
-        column![at, url, qr]
+        match &self.fingerprint {
+            // to compare with what the browser shows before trusting the certificate
+            Some(fingerprint) => {
+                let fingerprint = text(format!("certificate sha-256\n{fingerprint}"))
+                    .size(18)
+                    .width(400);
+                column![at, url, row![qr, fingerprint].align_y(Center).spacing(20.)]
+            }
+            None => column![at, url, qr],
+        }
     }
 
-    fn pick_button(&self) -> Button<Message> {
+    fn pick_button(&self) -> Button<'_, Message> {
         let working = self.is_working();
         let pt = text("pick other target")
             .align_x(Center)
@@ -181,12 +251,33 @@ impl State {
             })
     }
 
-    fn serve_button(&self) -> Button<Message> {
+    fn read_only_button(&self) -> Button<'_, Message> {
+        let lt = if self.read_only {
+            "browse and download only"
+        } else {
+            "anyone can change files"
+        };
+        // the server has to be restarted to change it
+        toggle_button(
+            lt,
+            self.read_only,
+            self.is_working(),
+            Message::ToggleReadOnly,
+        )
+    }
+
+    fn https_button(&self) -> Button<'_, Message> {
+        let https = self.fingerprint.is_some();
+        let lt = if https { "https" } else { "plain http" };
+        toggle_button(lt, https, self.is_working(), Message::ToggleHttps)
+    }
+
+    fn serve_button(&self) -> Button<'_, Message> {
         let working = self.is_working();
         let h = 80.;
         let lt = if working { "stop" } else { "serve" };
         let lt = text(lt).align_x(Center).align_y(Center).size(25.);
-        let launch = button(lt)
+        button(lt)
             .height(h)
             .width(h * 1.6)
             .style(move |_, _| {
@@ -217,7 +308,32 @@ impl State {
             .on_press(match &self.working_process {
                 Some(jh) => Message::Stop(jh.clone()),
                 None => Message::Launch,
-            });
-        launch
+            })
     }
 }
+
+/// a pill showing a setting, green when `on`, greyed out while serving
+fn toggle_button(label: &str, on: bool, working: bool, message: Message) -> Button<'_, Message> {
+    let lt = text(label).align_x(Center).align_y(Center).size(25.);
+    button(lt)
+        .style(move |_, _| {
+            let bg = if working {
+                Color::from_rgb(0.1, 0.1, 0.1)
+            } else if on {
+                Color::from_rgb(0.1, 0.6, 0.1)
+            } else {
+                Color::from_rgb(0.8, 0.5, 0.1)
+            };
+            let h = 70.;
+            Style {
+                background: Some(Background::Color(bg)),
+                border: Border {
+                    width: 3.,
+                    radius: Radius::new(h),
+                    color: Color::from_rgb(0., 0., 0.),
+                },
+                ..Default::default()
+            }
+        })
+        .on_press_maybe((!working).then_some(message))
+}
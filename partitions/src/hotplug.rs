@@ -0,0 +1,225 @@
+use crate::{MountResult, Partition, Policy, Result, mount_allowed, partitions};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    fs,
+    io::{Interest, unix::AsyncFd},
+    process::Command,
+    sync::mpsc,
+    time::{Interval, sleep},
+};
+
+/// how often `/sys/class/block` is listed when netlink is unavailable
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// udev needs a moment to probe a new device before lsblk knows its filesystem
+const SETTLE: Duration = Duration::from_millis(1500);
+const SYS_BLOCK: &str = "/sys/class/block";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotplugEvent {
+    Added(MountResult),
+    Removed(Partition),
+}
+
+/// what wakes the watcher up, kernel uevents or a timer
+enum Trigger {
+    Netlink(AsyncFd<OwnedFd>),
+    Poll(Interval, HashSet<String>),
+}
+
+impl Trigger {
+    fn new() -> Self {
+        match netlink() {
+            Ok(fd) => Self::Netlink(fd),
+            Err(_) => Self::Poll(tokio::time::interval(POLL_INTERVAL), HashSet::new()),
+        }
+    }
+
+    /// resolves once a block device may have come or gone
+    async fn changed(&mut self) {
+        match self {
+            Self::Netlink(fd) => loop {
+                let mut buf = [0u8; 8192];
+                let Ok(mut guard) = fd.readable().await else {
+                    return;
+                };
+                let read = guard.try_io(|fd| {
+                    let n = unsafe {
+                        libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0)
+                    };
+                    if n < 0 {
+                        Err(std::io::Error::last_os_error())
+                    } else {
+                        Ok(n as usize)
+                    }
+                });
+                if let Ok(Ok(n)) = read
+                    && is_block_event(&buf[..n])
+                {
+                    return;
+                }
+            },
+            Self::Poll(interval, known) => loop {
+                interval.tick().await;
+                let now = block_devices().await;
+                if now != *known {
+                    *known = now;
+                    return;
+                }
+            },
+        }
+    }
+}
+
+/// a socket receiving the kernel's uevents, readable without udev running
+fn netlink() -> std::io::Result<AsyncFd<OwnedFd>> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+            libc::NETLINK_KOBJECT_UEVENT,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let mut address: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    address.nl_family = libc::AF_NETLINK as u16;
+    address.nl_groups = 1;
+    let bound = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            (&address as *const libc::sockaddr_nl).cast(),
+            std::mem::size_of::<libc::sockaddr_nl>() as u32,
+        )
+    };
+    if bound < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    AsyncFd::with_interest(fd, Interest::READABLE)
+}
+
+/// uevents are `action@devpath` followed by nul separated `KEY=value` pairs
+fn is_block_event(message: &[u8]) -> bool {
+    let mut fields = message.split(|x| *x == 0).map(String::from_utf8_lossy);
+    let action = fields
+        .next()
+        .and_then(|x| x.split('@').next().map(str::to_string));
+    matches!(action.as_deref(), Some("add" | "remove" | "change"))
+        && fields.any(|x| x == "SUBSYSTEM=block")
+}
+
+async fn block_devices() -> HashSet<String> {
+    let mut names = HashSet::new();
+    if let Ok(mut entries) = fs::read_dir(SYS_BLOCK).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            names.insert(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    names
+}
+
+/// mounts partitions plugged in while it runs as `policy` allows and detaches the pulled ones
+///
+/// listens to kernel uevents over netlink, falling back to polling `/sys/class/block`. the
+/// lsblk, mount and umount it runs for a change wait for what `permit` resolves to, and run
+/// for as long as it is held
+pub fn watch<F, P>(
+    target: PathBuf,
+    policy: Policy,
+    permit: impl Fn() -> F + Send + 'static,
+) -> mpsc::Receiver<HotplugEvent>
+where
+    F: Future<Output = P> + Send,
+    P: Send,
+{
+    let (sender, receiver) = mpsc::channel(16);
+    tokio::spawn(async move {
+        let mut trigger = Trigger::new();
+        let mut known = {
+            let _permit = permit().await;
+            snapshot().await.unwrap_or_default()
+        };
+        loop {
+            trigger.changed().await;
+            sleep(SETTLE).await;
+            let _permit = permit().await;
+            let Ok(now) = snapshot().await else {
+                continue;
+            };
+            for (name, partition) in &now {
+                if known.contains_key(name) {
+                    continue;
+                }
+                let result = mount_allowed(partition.clone(), &target, &policy).await;
+                if sender.send(HotplugEvent::Added(result)).await.is_err() {
+                    return;
+                }
+            }
+            for (name, partition) in known {
+                if now.contains_key(&name) {
+                    continue;
+                }
+                detach(&partition, &target).await;
+                if sender.send(HotplugEvent::Removed(partition)).await.is_err() {
+                    return;
+                }
+            }
+            // mounts done above show up in the next snapshot
+            known = snapshot().await.unwrap_or(now);
+        }
+    });
+    receiver
+}
+
+async fn snapshot() -> Result<HashMap<String, Partition>> {
+    Ok(partitions()
+        .await?
+        .into_iter()
+        .map(|x| (x.name.clone(), x))
+        .collect())
+}
+
+/// lazily unmounts what a pulled drive left under `target`, its device is gone already
+async fn detach(partition: &Partition, target: &Path) {
+    // mount points are listed resolved
+    let Ok(target) = fs::canonicalize(target).await else {
+        return;
+    };
+    for at in partition
+        .mountpoints
+        .iter()
+        .filter(|x| x.starts_with(&target))
+    {
+        let _ = Command::new("umount").arg("-l").arg(at).output().await;
+        let _ = fs::remove_dir(at).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_uevents() {
+        let event = |x: &str| is_block_event(x.replace('|', "\0").as_bytes());
+        assert!(event(
+            "add@/devices/pci0000:00/usb1/1-1/host6/target6:0:0/6:0:0:0/block/sdb/sdb1|ACTION=add|SUBSYSTEM=block|DEVNAME=sdb1|DEVTYPE=partition"
+        ));
+        assert!(event(
+            "remove@/devices/virtual/block/loop0|ACTION=remove|SUBSYSTEM=block"
+        ));
+        assert!(!event(
+            "add@/devices/pci0000:00/usb1/1-1|ACTION=add|SUBSYSTEM=usb|DEVTYPE=usb_device"
+        ));
+        assert!(!event("bind@/devices/virtual/block/sdb|SUBSYSTEM=block"));
+    }
+}
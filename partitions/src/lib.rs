@@ -1,107 +1,176 @@
-use std::path::PathBuf;
-use tokio::io;
-use tokio::process::Command;
-
 use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    process::Output,
+};
+use tokio::{fs, process::Command};
+
+pub use hotplug::{HotplugEvent, watch};
+pub use lsblk::{Partition, partitions};
+pub use policy::{Policy, Rule, Skip};
 
-pub async fn refresh_partitions(path: PathBuf) -> io::Result<()> {
-    let lsblk = Lsblk::get().await?;
-    for p in lsblk.partitions().iter() {
-        let mut path = path.clone();
-        path.push(&p.name);
-        if p.is_mounted() {
-            p.umount(path.clone()).await?;
+mod hotplug;
+mod lsblk;
+mod policy;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// an external command exited unsuccessfully
+    Command {
+        command: &'static str,
+        code: Option<i32>,
+        stderr: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    fn command(command: &'static str, output: &Output) -> Self {
+        Self::Command {
+            command,
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
         }
-        p.mount(path).await?;
     }
-    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Lsblk {
-    blockdevices: Vec<BlockDevice>,
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(x) => write!(f, "{x}"),
+            Self::Json(x) => write!(f, "unexpected lsblk output : {x}"),
+            Self::Command {
+                command,
+                code,
+                stderr,
+            } => match code {
+                Some(code) => write!(f, "{command} exited with {code} : {stderr}"),
+                None => write!(f, "{command} was killed : {stderr}"),
+            },
+        }
+    }
 }
 
-const MINIMUM_STORAGE_SIZE_IN_GIGABYTES: f32 = 7.;
+impl std::error::Error for Error {}
 
-impl Lsblk {
-    async fn get() -> io::Result<Self> {
-        let bytes = Command::new("lsblk").arg("--json").output().await?.stdout;
-        let result = serde_json::from_slice::<Self>(&bytes).unwrap();
-        Ok(result)
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
     }
+}
 
-    fn partitions(self) -> Vec<Partition> {
-        let Self { blockdevices } = self;
-        blockdevices
-            .into_iter()
-            .flat_map(|BlockDevice { children }| children)
-            .filter(Partition::valid_size)
-            .filter(Partition::is_system_free)
-            .collect()
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct BlockDevice {
-    children: Vec<Partition>,
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Mounted(PathBuf),
+    AlreadyMounted(PathBuf),
+    Skipped(Skip),
+    Failed(String),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Partition {
-    name: String,
-    size: String,
-    mountpoints: Vec<Option<PathBuf>>,
+/// what [`mount_all`] did with one partition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MountResult {
+    pub partition: Partition,
+    pub outcome: Outcome,
 }
 
-impl Partition {
-    fn valid_size(&self) -> bool {
-        let bigger_than = move |num: f32| {
-            self.size[0..self.size.len() - 1]
-                .parse::<f32>()
-                .is_ok_and(|x| x > num)
-        };
-        if self.size.ends_with("T") && bigger_than(MINIMUM_STORAGE_SIZE_IN_GIGABYTES / 1000.) {
-            return true;
-        }
-        self.size.ends_with("G") && bigger_than(MINIMUM_STORAGE_SIZE_IN_GIGABYTES)
-    }
-    fn is_system_free(&self) -> bool {
-        !self.mountpoints.iter().any(|x| {
-            x.as_ref()
-                .is_some_and(|s| matches!(s.to_str().unwrap(), "/" | "/home" | "/boot"))
-        })
+/// mounts every partition `policy` allows under `target`, leaving mounted ones alone
+pub async fn mount_all(target: &Path, policy: &Policy) -> Result<Vec<MountResult>> {
+    let mut results = Vec::new();
+    for partition in partitions().await? {
+        results.push(mount_allowed(partition, target, policy).await);
     }
+    Ok(results)
+}
 
-    fn dev_path(&self) -> PathBuf {
-        let mut dev_path = PathBuf::new();
-        dev_path.push("/dev");
-        dev_path.push(self.name.clone());
-        dev_path
+/// mounts `partition` under `target` if `policy` allows it and it is not mounted yet
+pub async fn mount_allowed(partition: Partition, target: &Path, policy: &Policy) -> MountResult {
+    let outcome = if let Err(skip) = policy.check(&partition) {
+        Outcome::Skipped(skip)
+    } else if let Some(at) = partition.mountpoints.first() {
+        Outcome::AlreadyMounted(at.clone())
+    } else {
+        match mount(&partition, target, policy.read_only).await {
+            Ok(at) => Outcome::Mounted(at),
+            Err(err) => Outcome::Failed(err.to_string()),
+        }
+    };
+    MountResult { partition, outcome }
+}
+
+/// directory name of `partition` under the target, its label when it has a usable one
+pub fn mount_name(partition: &Partition) -> String {
+    partition
+        .label
+        .as_deref()
+        .map(|x| x.replace(['/', '\\'], "_"))
+        .map(|x| x.trim_start_matches('.').trim().to_string())
+        .filter(|x| !x.is_empty())
+        .unwrap_or_else(|| partition.name.clone())
+}
+
+/// mounts `partition` in a directory of its own under `target` and returns that directory
+pub async fn mount(partition: &Partition, target: &Path, read_only: bool) -> Result<PathBuf> {
+    let mut at = target.join(mount_name(partition));
+    // two drives can share a label, the second one gets its device name appended
+    let taken = match fs::read_dir(&at).await {
+        Ok(mut entries) => entries.next_entry().await?.is_some(),
+        Err(_) => false,
+    };
+    if taken {
+        at = target.join(format!("{} ({})", mount_name(partition), partition.name));
     }
+    fs::create_dir_all(&at).await?;
 
-    fn is_mounted(&self) -> bool {
-        self.mountpoints.iter().any(|x| x.as_ref().is_some())
+    let mut command = Command::new("mount");
+    if read_only {
+        command.args(["-o", "ro"]);
+    }
+    let output = command.arg(&partition.dev_path).arg(&at).output().await?;
+    if !output.status.success() {
+        let _ = fs::remove_dir(&at).await;
+        return Err(Error::command("mount", &output));
     }
+    Ok(at)
+}
 
-    async fn mount(&self, path: PathBuf) -> io::Result<()> {
-        let _ = tokio::fs::create_dir(&path).await;
-        let _ = Command::new("mount")
-            .args([self.dev_path(), path])
-            .spawn()?
-            .wait()
-            .await?;
-        Ok(())
+/// unmounts every mountpoint of `partition` and removes the emptied directories
+pub async fn unmount(partition: &Partition) -> Result<()> {
+    for at in &partition.mountpoints {
+        let output = Command::new("umount").arg(at).output().await?;
+        if !output.status.success() {
+            return Err(Error::command("umount", &output));
+        }
+        let _ = fs::remove_dir(at).await;
     }
+    Ok(())
+}
 
-    async fn umount(&self, mut path: PathBuf) -> io::Result<()> {
-        path.push(&self.name);
-        let _ = Command::new("umount")
-            .arg(self.dev_path())
-            .spawn()?
-            .wait()
-            .await?;
-        let _ = tokio::fs::remove_dir(path).await;
-        Ok(())
+/// flushes pending writes, unmounts and powers off the drive holding `partition`
+pub async fn eject(partition: &Partition) -> Result<()> {
+    let output = Command::new("sync").output().await?;
+    if !output.status.success() {
+        return Err(Error::command("sync", &output));
+    }
+    unmount(partition).await?;
+    let output = Command::new("eject")
+        .arg(&partition.dev_path)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(Error::command("eject", &output));
     }
+    Ok(())
 }
@@ -0,0 +1,177 @@
+use crate::{Error, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// columns asked from lsblk, `PATH` spares guessing `/dev/mapper` names
+const COLUMNS: &str = "NAME,PATH,SIZE,FSTYPE,LABEL,UUID,MOUNTPOINTS,RM";
+
+/// a block device holding a filesystem, be it a partition or a whole disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Partition {
+    pub name: String,
+    pub dev_path: PathBuf,
+    /// bytes
+    pub size: u64,
+    pub fstype: String,
+    pub label: Option<String>,
+    pub uuid: Option<String>,
+    pub mountpoints: Vec<PathBuf>,
+    pub removable: bool,
+}
+
+impl Partition {
+    pub fn is_mounted(&self) -> bool {
+        !self.mountpoints.is_empty()
+    }
+}
+
+#[derive(Deserialize)]
+struct Lsblk {
+    blockdevices: Vec<Device>,
+}
+
+#[derive(Deserialize)]
+struct Device {
+    name: String,
+    path: Option<PathBuf>,
+    #[serde(deserialize_with = "number")]
+    size: u64,
+    fstype: Option<String>,
+    label: Option<String>,
+    uuid: Option<String>,
+    #[serde(default)]
+    mountpoints: Vec<Option<PathBuf>>,
+    #[serde(deserialize_with = "flag")]
+    rm: bool,
+    #[serde(default)]
+    children: Vec<Device>,
+}
+
+/// util-linux before 2.37 prints every value as a string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Loose<T> {
+    Typed(T),
+    Text(String),
+}
+
+fn number<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u64, D::Error> {
+    match Loose::<u64>::deserialize(deserializer)? {
+        Loose::Typed(x) => Ok(x),
+        Loose::Text(x) => x.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<bool, D::Error> {
+    match Loose::<bool>::deserialize(deserializer)? {
+        Loose::Typed(x) => Ok(x),
+        Loose::Text(x) => Ok(x == "1"),
+    }
+}
+
+impl Device {
+    /// every filesystem in the tree, partitions inherit removability from their disk
+    fn flatten(self, removable: bool, into: &mut Vec<Partition>) {
+        let removable = removable || self.rm;
+        if let Some(fstype) = self.fstype.filter(|x| !x.is_empty()) {
+            into.push(Partition {
+                dev_path: self
+                    .path
+                    .unwrap_or_else(|| PathBuf::from("/dev").join(&self.name)),
+                name: self.name,
+                size: self.size,
+                fstype,
+                label: self.label.filter(|x| !x.is_empty()),
+                uuid: self.uuid,
+                mountpoints: self.mountpoints.into_iter().flatten().collect(),
+                removable,
+            });
+        }
+        for child in self.children {
+            child.flatten(removable, into);
+        }
+    }
+}
+
+/// the filesystems of `lsblk --json` output
+pub fn parse(json: &[u8]) -> Result<Vec<Partition>> {
+    let Lsblk { blockdevices } = serde_json::from_slice(json)?;
+    let mut partitions = Vec::new();
+    for device in blockdevices {
+        device.flatten(false, &mut partitions);
+    }
+    Ok(partitions)
+}
+
+/// the filesystems currently attached to the host
+pub async fn partitions() -> Result<Vec<Partition>> {
+    let output = Command::new("lsblk")
+        .args(["--json", "--bytes", "-o", COLUMNS])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(Error::command("lsblk", &output));
+    }
+    parse(&output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn laptop_fixture() {
+        let partitions = parse(include_bytes!("../fixtures/laptop.json")).unwrap();
+        let names = partitions
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "sda1",
+                "nvme0n1p1",
+                "nvme0n1p2",
+                "nvme0n1p3",
+                "nvme0n1p4",
+                "sdb",
+                "sdc"
+            ]
+        );
+
+        let stick = &partitions[0];
+        assert_eq!(stick.dev_path, PathBuf::from("/dev/sda1"));
+        assert_eq!(stick.size, 31003246592);
+        assert_eq!(stick.label.as_deref(), Some("KINGSTON"));
+        assert!(stick.removable && !stick.is_mounted());
+
+        let home = &partitions[4];
+        assert_eq!(
+            home.mountpoints,
+            ["/home", "/var/lib/docker"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn old_util_linux_fixture() {
+        let partitions = parse(include_bytes!("../fixtures/old-util-linux.json")).unwrap();
+        assert_eq!(partitions.len(), 2);
+        assert_eq!(partitions[0].size, 250058301440);
+        assert!(!partitions[0].removable);
+        assert!(partitions[1].removable);
+    }
+
+    #[test]
+    fn luks_fixture() {
+        let partitions = parse(include_bytes!("../fixtures/luks.json")).unwrap();
+        let backup = partitions.iter().find(|x| x.name == "backup").unwrap();
+        assert_eq!(backup.dev_path, PathBuf::from("/dev/mapper/backup"));
+        assert_eq!(backup.fstype, "xfs");
+    }
+
+    #[test]
+    fn broken_json_is_an_error() {
+        assert!(parse(b"{\"blockdevices\": [{\"name\": 3}]}").is_err());
+    }
+}
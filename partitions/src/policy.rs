@@ -0,0 +1,164 @@
+use crate::Partition;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// smaller filesystems are usually recovery or firmware partitions
+const DEFAULT_MIN_SIZE: u64 = 7 * 1024 * 1024 * 1024;
+/// a filesystem mounted at any of these belongs to the host
+const SYSTEM_MOUNTPOINTS: [&str; 5] = ["/", "/home", "/boot", "/boot/efi", "[SWAP]"];
+/// filesystems `mount` can not use directly
+const UNMOUNTABLE: [&str; 5] = [
+    "swap",
+    "crypto_LUKS",
+    "LVM2_member",
+    "linux_raid_member",
+    "zfs_member",
+];
+
+/// picks partitions by what they are rather than by where they sit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    Uuid(String),
+    Label(String),
+    FsType(String),
+}
+
+impl Rule {
+    pub fn matches(&self, partition: &Partition) -> bool {
+        match self {
+            Self::Uuid(x) => partition
+                .uuid
+                .as_ref()
+                .is_some_and(|uuid| uuid.eq_ignore_ascii_case(x)),
+            Self::Label(x) => partition.label.as_ref() == Some(x),
+            Self::FsType(x) => partition.fstype == *x,
+        }
+    }
+}
+
+/// why a partition was left alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Skip {
+    System,
+    Unmountable,
+    TooSmall,
+    Denied,
+    NotAllowed,
+}
+
+/// which partitions get mounted and how, deny rules win over allow rules
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// when empty every partition not denied is allowed
+    pub allow: Vec<Rule>,
+    pub deny: Vec<Rule>,
+    pub read_only: bool,
+    /// bytes
+    pub min_size: u64,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            read_only: false,
+            min_size: DEFAULT_MIN_SIZE,
+        }
+    }
+}
+
+impl Policy {
+    pub fn allow(mut self, rule: Rule) -> Self {
+        self.allow.push(rule);
+        self
+    }
+    pub fn deny(mut self, rule: Rule) -> Self {
+        self.deny.push(rule);
+        self
+    }
+    pub fn read_only(self, read_only: bool) -> Self {
+        Self { read_only, ..self }
+    }
+    pub fn min_size(self, min_size: u64) -> Self {
+        Self { min_size, ..self }
+    }
+
+    pub fn check(&self, partition: &Partition) -> Result<(), Skip> {
+        let system = partition
+            .mountpoints
+            .iter()
+            .any(|x| SYSTEM_MOUNTPOINTS.iter().any(|s| x == Path::new(s)));
+        if system {
+            return Err(Skip::System);
+        }
+        if UNMOUNTABLE.contains(&partition.fstype.as_str()) {
+            return Err(Skip::Unmountable);
+        }
+        if partition.size < self.min_size {
+            return Err(Skip::TooSmall);
+        }
+        if self.deny.iter().any(|x| x.matches(partition)) {
+            return Err(Skip::Denied);
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|x| x.matches(partition)) {
+            return Err(Skip::NotAllowed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policy_keeps_off_the_system() {
+        let partitions = crate::lsblk::parse(include_bytes!("../fixtures/laptop.json")).unwrap();
+        let checks = partitions
+            .iter()
+            .map(|x| (x.name.as_str(), Policy::default().check(x)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            checks,
+            [
+                ("sda1", Ok(())),
+                ("nvme0n1p1", Err(Skip::System)),
+                ("nvme0n1p2", Err(Skip::System)),
+                ("nvme0n1p3", Err(Skip::System)),
+                ("nvme0n1p4", Err(Skip::System)),
+                ("sdb", Ok(())),
+                ("sdc", Err(Skip::TooSmall)),
+            ]
+        );
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let partitions = crate::lsblk::parse(include_bytes!("../fixtures/laptop.json")).unwrap();
+        let policy = Policy::default()
+            .min_size(0)
+            .allow(Rule::FsType("vfat".into()))
+            .allow(Rule::Uuid("6a1c-9f3e".into()))
+            .deny(Rule::Label("TINY".into()));
+        let allowed = partitions
+            .iter()
+            .filter(|x| policy.check(x).is_ok())
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(allowed, ["sda1"]);
+    }
+
+    #[test]
+    fn luks_containers_are_unmountable() {
+        let partitions = crate::lsblk::parse(include_bytes!("../fixtures/luks.json")).unwrap();
+        let checks = partitions
+            .iter()
+            .map(|x| Policy::default().check(x))
+            .collect::<Vec<_>>();
+        assert_eq!(checks, [Err(Skip::Unmountable), Ok(())]);
+    }
+}
@@ -1,6 +1,7 @@
 use axum::{
     Json,
     extract::multipart::MultipartError,
+    http::StatusCode,
     response::{self, IntoResponse},
 };
 use serde::Serialize;
@@ -16,13 +17,41 @@ pub type ServerResult<T> = std::result::Result<T, ServerError>;
 pub enum ServerError {
     FfmpagSpawn(PathBuf),
     FfmpagWait(PathBuf),
+    Ffmpeg(PathBuf, String),
+    OutputExists(PathBuf),
     Join(String),
     Io(String),
+    /// what was asked for is not there
+    NotFound(String),
     Copy,
     NonePort,
     NonePathFilename,
     MultiPart(String),
     StripPrefixError,
+    Image(String),
+    Partitions(String),
+    PartitionNotFound(String),
+    PartitionSkipped(partitions::Skip),
+    NotRemovable(String),
+    /// the upload and how many bytes it could have taken
+    InsufficientStorage(PathBuf, u64),
+    ShareNotFound(PathBuf),
+    ReadOnly(PathBuf),
+    Forbidden(PathBuf),
+    Tls(String),
+    /// the token of a share link that expired, ran out of downloads or never was
+    LinkNotFound(String),
+    /// the share link needs its password first
+    Locked(String),
+    NotAnArchive(PathBuf),
+    /// the archive holds more entries, or unpacks to more bytes, than an extraction may write
+    ExtractLimit(PathBuf),
+    /// the copy does not hash like its source
+    ChecksumMismatch(PathBuf),
+    /// only files have checksums
+    NotAFile(PathBuf),
+    /// only the users the server names as admins may do that
+    AdminRequired,
 }
 
 impl From<JoinError> for ServerError {
@@ -33,7 +62,10 @@ impl From<JoinError> for ServerError {
 
 impl From<io::Error> for ServerError {
     fn from(value: io::Error) -> Self {
-        Self::Io(value.to_string())
+        match value.kind() {
+            io::ErrorKind::NotFound => Self::NotFound(value.to_string()),
+            _ => Self::Io(value.to_string()),
+        }
     }
 }
 
@@ -49,6 +81,18 @@ impl From<StripPrefixError> for ServerError {
     }
 }
 
+impl From<image::ImageError> for ServerError {
+    fn from(value: image::ImageError) -> Self {
+        Self::Image(value.to_string())
+    }
+}
+
+impl From<partitions::Error> for ServerError {
+    fn from(value: partitions::Error) -> Self {
+        Self::Partitions(value.to_string())
+    }
+}
+
 impl Display for ServerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let sj = serde_json::json!(self);
@@ -60,6 +104,16 @@ impl std::error::Error for ServerError {}
 
 impl IntoResponse for ServerError {
     fn into_response(self) -> response::Response {
-        Json(self).into_response()
+        let status = match self {
+            Self::InsufficientStorage(..) => StatusCode::INSUFFICIENT_STORAGE,
+            Self::Forbidden(_) | Self::ReadOnly(_) | Self::AdminRequired => StatusCode::FORBIDDEN,
+            Self::ShareNotFound(_) | Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::NotAFile(_) => StatusCode::BAD_REQUEST,
+            Self::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Locked(_) => StatusCode::UNAUTHORIZED,
+            Self::LinkNotFound(_) => StatusCode::GONE,
+            _ => StatusCode::OK,
+        };
+        (status, Json(self)).into_response()
     }
 }
@@ -0,0 +1,320 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+    extract,
+    jobs::{Job, Progress},
+    quota,
+    thumbnail::partial_path,
+};
+use axum::{
+    Json,
+    body::Body,
+    extract::{RawQuery, State},
+    http::{HeaderValue, header},
+    response::{IntoResponse, Response},
+};
+use common::{AUDIO_X, ArchiveFormat, IMAGE_X, NewArchive, VIDEO_X};
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+use zip::{
+    CompressionMethod, ZipWriter,
+    write::{SimpleFileOptions, StreamWriter},
+};
+
+/// how much of the archive is made ahead of what the client took
+const BUFFER: usize = 256 * 1024;
+
+/// deflating these again only costs time
+const COMPRESSED_X: [&str; 8] = ["zip", "gz", "xz", "zst", "bz2", "7z", "rar", "pdf"];
+
+/// the format and the paths of `format=zip&path=a&path=b`
+pub(crate) fn query(query: Option<&str>) -> (ArchiveFormat, Vec<PathBuf>) {
+    let mut format = ArchiveFormat::default();
+    let mut paths = Vec::new();
+    for (key, value) in form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        match key.as_ref() {
+            "format" => {
+                format = ArchiveFormat::ALL
+                    .into_iter()
+                    .find(|x| x.extension() == value)
+                    .unwrap_or_default();
+            }
+            "path" => paths.push(PathBuf::from(value.as_ref())),
+            _ => (),
+        }
+    }
+    (format, paths)
+}
+
+/// every file and directory under the selected paths, packed while it is sent
+pub async fn archive(
+    State(Context { shares, .. }): State<Context>,
+    user: User,
+    RawQuery(raw): RawQuery,
+) -> ServerResult<Response> {
+    let (format, paths) = query(raw.as_deref());
+    let targets = paths
+        .iter()
+        .map(|x| shares.resolve(x, &user))
+        .collect::<ServerResult<Vec<_>>>()?;
+    Ok(stream(format, targets))
+}
+
+/// writes the archive in `to` in the background, the job id is returned right away
+pub async fn create(
+    State(Context {
+        shares,
+        state_dir,
+        quotas,
+        jobs,
+        ..
+    }): State<Context>,
+    user: User,
+    Json(NewArchive {
+        targets,
+        to,
+        name,
+        format,
+        naming,
+    }): Json<NewArchive>,
+) -> ServerResult<Json<u64>> {
+    let dir = shares.writable(&to, &user)?;
+    let file_name = format!("{name}.{}", format.extension());
+    // a name, not a path leading out of `to`
+    if Path::new(&file_name).file_name().and_then(|x| x.to_str()) != Some(&file_name) {
+        return Err(ServerError::Forbidden(PathBuf::from(file_name)));
+    }
+    let targets = targets
+        .iter()
+        .map(|x| shares.resolve(x, &user))
+        .collect::<ServerResult<Vec<_>>>()?;
+    // the archive would end up packing itself
+    if targets.iter().any(|x| dir.starts_with(x)) {
+        return Err(ServerError::Forbidden(to));
+    }
+    let path = extract::output(dir.join(&file_name), naming)?;
+
+    let mut total = 0;
+    for target in &targets {
+        let metadata = tokio::fs::metadata(target).await?;
+        total += if metadata.is_dir() {
+            quota::dir_size(target).await
+        } else {
+            metadata.len()
+        };
+    }
+    let remaining = quotas
+        .remaining(&shares, &state_dir, &to, user.0.as_deref())
+        .await?;
+    // stored, the archive is about as big as what it holds
+    quota::check(&path, total, remaining)?;
+
+    let job = jobs.start(format!("archive {file_name}"), total);
+    let id = job.id();
+    tokio::task::spawn_blocking(move || {
+        // nobody sees a half written archive under its final name
+        let partial = partial_path(&path);
+        let result = File::create(&partial).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(format, &targets, &mut writer, Some(&job))?;
+            writer.flush()?;
+            fs::rename(&partial, &path)
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        job.finish(result.map_err(Into::into));
+    });
+    Ok(Json(id))
+}
+
+/// `targets` on disk go in the archive under their own names
+pub(crate) fn stream(format: ArchiveFormat, targets: Vec<PathBuf>) -> Response {
+    let name = match targets.as_slice() {
+        [target] => target.file_name(),
+        _ => targets.first().and_then(|x| x.parent()?.file_name()),
+    }
+    .and_then(|x| x.to_str())
+    .unwrap_or("webls")
+    // the name goes in a quoted header value
+    .replace(
+        |x: char| !x.is_ascii() || x.is_ascii_control() || x == '"',
+        "_",
+    );
+
+    let (reader, writer) = tokio::io::duplex(BUFFER);
+    let writer = SyncIoBridge::new(writer);
+    tokio::task::spawn_blocking(move || {
+        // the client only sees a truncated archive, the server log tells why
+        if let Err(err) = write(format, &targets, writer, None) {
+            tracing::warn!("archive of {targets:?} cut short : {err}");
+        }
+    });
+
+    let disposition = format!("attachment; filename=\"{name}.{}\"", format.extension());
+    let content_type = match format {
+        ArchiveFormat::Zip => "application/zip",
+        ArchiveFormat::Tar => "application/x-tar",
+        ArchiveFormat::TarZst => "application/zstd",
+    };
+    (
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            (
+                header::CONTENT_DISPOSITION,
+                HeaderValue::from_str(&disposition)
+                    .unwrap_or(HeaderValue::from_static("attachment")),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+        .into_response()
+}
+
+/// the bytes of the files read so far are the progress of `job`
+fn write<W: Write>(
+    format: ArchiveFormat,
+    targets: &[PathBuf],
+    writer: W,
+    job: Option<&Job>,
+) -> io::Result<()> {
+    match format {
+        ArchiveFormat::Zip => pack(
+            Archive::Zip(Box::new(ZipWriter::new_stream(writer))),
+            targets,
+            job,
+        ),
+        ArchiveFormat::Tar => pack(Archive::Tar(tar::Builder::new(writer)), targets, job),
+        ArchiveFormat::TarZst => {
+            let mut encoder = zstd::Encoder::new(writer, 0)?;
+            pack(Archive::Tar(tar::Builder::new(&mut encoder)), targets, job)?;
+            encoder.finish().map(|_| ())
+        }
+    }
+}
+
+fn pack<W: Write>(
+    mut archive: Archive<W>,
+    targets: &[PathBuf],
+    job: Option<&Job>,
+) -> io::Result<()> {
+    for target in targets {
+        let name = target.file_name().map(PathBuf::from).unwrap_or_default();
+        walk(&mut archive, target, &name, job)?;
+    }
+    archive.finish()
+}
+
+fn walk<W: Write>(
+    archive: &mut Archive<W>,
+    real: &Path,
+    name: &Path,
+    job: Option<&Job>,
+) -> io::Result<()> {
+    let metadata = fs::metadata(real)?;
+    if !metadata.is_dir() {
+        return archive.file(real, name, &metadata, job);
+    }
+    archive.dir(real, name)?;
+    let mut children = fs::read_dir(real)?.collect::<io::Result<Vec<_>>>()?;
+    children.sort_by_key(|x| x.file_name());
+    for child in children {
+        // a linked directory could hold the archive itself, or loop forever
+        if child.file_type()?.is_symlink() && child.path().is_dir() {
+            continue;
+        }
+        walk(archive, &child.path(), &name.join(child.file_name()), job)?;
+    }
+    Ok(())
+}
+
+enum Archive<W: Write> {
+    Zip(Box<ZipWriter<StreamWriter<W>>>),
+    Tar(tar::Builder<W>),
+}
+
+impl<W: Write> Archive<W> {
+    fn dir(&mut self, real: &Path, name: &Path) -> io::Result<()> {
+        match self {
+            Self::Zip(zip) => zip
+                .add_directory(zip_name(name), SimpleFileOptions::default())
+                .map_err(io::Error::other),
+            Self::Tar(tar) => tar.append_dir(name, real),
+        }
+    }
+
+    fn file(
+        &mut self,
+        real: &Path,
+        name: &Path,
+        metadata: &Metadata,
+        job: Option<&Job>,
+    ) -> io::Result<()> {
+        let mut file = Progress(File::open(real)?, job);
+        match self {
+            Self::Zip(zip) => {
+                let options = SimpleFileOptions::default()
+                    .compression_method(compression(name))
+                    .large_file(metadata.len() >= u32::MAX as u64);
+                zip.start_file(zip_name(name), options)
+                    .map_err(io::Error::other)?;
+                io::copy(&mut file, zip)?;
+                Ok(())
+            }
+            Self::Tar(tar) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(metadata);
+                tar.append_data(&mut header, name, file)
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Zip(zip) => zip.finish().map(|_| ()).map_err(io::Error::other),
+            Self::Tar(tar) => tar.into_inner().map(|_| ()),
+        }
+    }
+}
+
+/// zip paths are separated by `/` whatever the server runs on
+fn zip_name(name: &Path) -> String {
+    name.iter()
+        .map(|x| x.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn compression(name: &Path) -> CompressionMethod {
+    let extension = name
+        .extension()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let compressed = [&VIDEO_X[..], &AUDIO_X, &IMAGE_X, &COMPRESSED_X]
+        .iter()
+        .any(|xs| xs.contains(&extension.as_str()));
+    if compressed {
+        CompressionMethod::Stored
+    } else {
+        CompressionMethod::Deflated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_query() {
+        let (format, paths) = query(Some("format=tar&path=a%20b&path=c%2Fd.txt"));
+        assert_eq!(format, ArchiveFormat::Tar);
+        assert_eq!(paths, [PathBuf::from("a b"), PathBuf::from("c/d.txt")]);
+        assert_eq!(query(None), (ArchiveFormat::Zip, Vec::new()));
+    }
+}
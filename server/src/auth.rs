@@ -0,0 +1,138 @@
+use crate::app_error::{ServerError, ServerResult};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier, password_hash::SaltString};
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderMap, StatusCode, header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+/// the argon2 hash, in its PHC string form, to give as the password of a user
+pub fn hash_password(password: &str) -> String {
+    let mut salt = [0; 16];
+    getrandom::fill(&mut salt).expect("no random source for a salt");
+    let salt = SaltString::encode_b64(&salt).expect("16 bytes make a valid salt");
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("default argon2 parameters hash any password")
+        .to_string()
+}
+
+/// whether `hash` is something [`hash_password`] could have made
+pub fn is_password_hash(hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|x| x.algorithm.as_str().starts_with("argon2"))
+}
+
+/// whether `password` is the one [`hash_password`] turned into `hash`, slow on purpose
+pub(crate) fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// user names and the argon2 hashes of their passwords, nobody has to log in when empty
+pub(crate) struct Users {
+    hashes: HashMap<String, String>,
+    /// digests of the credentials that matched already, argon2 is far too slow for every request
+    verified: Mutex<HashSet<[u8; 32]>>,
+}
+
+impl Users {
+    pub(crate) fn new(hashes: HashMap<String, String>) -> Self {
+        Self {
+            hashes,
+            verified: Mutex::default(),
+        }
+    }
+
+    /// whether `password` is the one of `user`, the hash comparison runs in constant time
+    async fn check(self: Arc<Self>, user: String, password: String) -> bool {
+        let digest: [u8; 32] = Sha256::new()
+            .chain_update(&user)
+            .chain_update([0])
+            .chain_update(&password)
+            .finalize()
+            .into();
+        if self.verified.lock().unwrap().contains(&digest) {
+            return true;
+        }
+        let matches = tokio::task::spawn_blocking(move || {
+            let matches = self
+                .hashes
+                .get(&user)
+                .is_some_and(|hash| verify_password(hash, &password));
+            if matches {
+                // only right passwords land here, at most one a user
+                self.verified.lock().unwrap().insert(digest);
+            }
+            matches
+        });
+        matches.await.unwrap_or(false)
+    }
+}
+
+/// the user name and password of basic auth credentials
+pub(crate) fn credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let credentials = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let credentials = String::from_utf8(BASE64_STANDARD.decode(credentials).ok()?).ok()?;
+    let (user, password) = credentials.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+/// who sent a request, only ever a user whose password [`basic_auth`] checked
+#[derive(Debug, Clone, Default)]
+pub(crate) struct User(pub Option<String>);
+
+impl User {
+    /// fails unless this is one of `admins`, everyone is one when nobody has to log in
+    pub(crate) fn admin(&self, admins: &Option<HashSet<String>>) -> ServerResult<()> {
+        match (admins, &self.0) {
+            (None, _) => Ok(()),
+            (Some(admins), Some(user)) if admins.contains(user) => Ok(()),
+            _ => Err(ServerError::AdminRequired),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for User {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().cloned().unwrap_or_default())
+    }
+}
+
+/// lets a request through only with the credentials of one of `users`, as that [`User`]
+pub(crate) async fn basic_auth(
+    State(users): State<Arc<Users>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if users.hashes.is_empty() {
+        return next.run(request).await;
+    }
+    if let Some((user, password)) = credentials(request.headers())
+        && users.check(user.clone(), password).await
+    {
+        request.extensions_mut().insert(User(Some(user)));
+        return next.run(request).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Basic realm=\"webls\"")],
+    )
+        .into_response()
+}
@@ -2,16 +2,18 @@ use crate::mp4::par_mp4_remux;
 use crate::{
     Context,
     app_error::{ServerError, ServerResult},
+    auth::User,
+    quota::{self, Quotas},
+    shares::Shares,
+    thumbnail::partial_path,
 };
 use axum::{
     Json,
-    extract::{Multipart, State},
+    extract::{Multipart, State, multipart::Field},
+    http::{HeaderMap, header},
 };
 use common::{Unit, UnitKind, VIDEO_X};
-use std::{
-    path::{Path, PathBuf},
-    str::FromStr,
-};
+use std::path::{Path, PathBuf};
 use tokio::fs;
 use tokio::{
     fs::File,
@@ -19,13 +21,15 @@ use tokio::{
 };
 
 pub async fn cp(
-    State(Context { target_dir }): State<Context>,
+    State(Context { shares, .. }): State<Context>,
+    user: User,
     Json((targets, to)): Json<(Vec<PathBuf>, PathBuf)>,
 ) -> ServerResult<()> {
     use tokio::{fs::copy, task::JoinSet};
-    let to = target_dir.join(to);
+    let to = shares.writable(&to, &user)?;
     let mut set = JoinSet::new();
-    for base in targets.into_iter().map(|x| target_dir.join(x)) {
+    for target in targets {
+        let base = shares.resolve(&target, &user)?;
         let name = path_file_name(&base)?;
         set.spawn(copy(base, to.join(name)));
     }
@@ -48,21 +52,35 @@ fn path_file_name(base: &Path) -> ServerResult<String> {
 }
 
 pub async fn mv(
-    State(Context { target_dir }): State<Context>,
+    State(Context {
+        shares,
+        state_dir,
+        quotas,
+        ..
+    }): State<Context>,
+    user: User,
     Json((targets, to)): Json<(Vec<PathBuf>, PathBuf)>,
 ) -> ServerResult<()> {
     use tokio::task::JoinSet;
-    let to = target_dir.join(to);
+    let dir = shares.writable(&to, &user)?;
     let mut set = JoinSet::new();
-    for base in targets.into_iter().map(|x| target_dir.join(x)) {
+    for target in targets {
+        let base = shares.removable(&target, &user)?;
         let name = path_file_name(&base)?;
-        set.spawn(cut(base, to.join(name)));
+        let moved = to.join(&name);
+        let cut = cut(base, dir.join(name));
+        set.spawn(async move { cut.await.map(|()| (target, moved)) });
     }
 
+    let mut result = Ok(());
     while let Some(x) = set.join_next().await {
-        let _ = x?;
+        match x? {
+            // uploads count against their user wherever they go
+            Ok((from, to)) => quotas.moved(&state_dir, &from, &to).await,
+            Err(err) => result = Err(err),
+        }
     }
-    Ok(())
+    result
 }
 
 pub async fn cut(from: PathBuf, to: PathBuf) -> ServerResult<()> {
@@ -73,7 +91,8 @@ pub async fn cut(from: PathBuf, to: PathBuf) -> ServerResult<()> {
 }
 
 pub async fn rm(
-    State(Context { target_dir }): State<Context>,
+    State(Context { shares, .. }): State<Context>,
+    user: User,
     Json(bases): Json<Vec<Unit>>,
 ) -> ServerResult<()> {
     use {
@@ -81,7 +100,7 @@ pub async fn rm(
         tokio::fs::{remove_dir_all, remove_file},
     };
     for base in bases.into_iter() {
-        let path = target_dir.join(base.path);
+        let path = shares.removable(&base.path, &user)?;
         match base.kind {
             UnitKind::Dirctory => {
                 remove_dir_all(path).await?;
@@ -95,51 +114,165 @@ pub async fn rm(
     Ok(())
 }
 
+/// how many bytes are written between checks of the actual free space
+const FREE_SPACE_CHECK: u64 = 64 * 1024 * 1024;
+/// room for the boundaries and part headers around the files of an upload, which count towards
+/// its content length but are never stored, enough for a few thousand files
+const MULTIPART_OVERHEAD: u64 = 1024 * 1024;
+
 pub async fn upload(
-    State(Context { target_dir }): State<Context>,
+    State(Context {
+        shares,
+        state_dir,
+        processes,
+        quotas,
+        ..
+    }): State<Context>,
+    user: User,
+    headers: HeaderMap,
     multipart: Multipart,
 ) -> ServerResult<()> {
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|x| x.to_str().ok()?.parse::<u64>().ok());
     let mut data = multipart;
     let mut non_mp4_paths = Vec::new();
+    let stored = store_fields(
+        &mut data,
+        &shares,
+        &state_dir,
+        &quotas,
+        &user,
+        length,
+        &mut non_mp4_paths,
+    )
+    .await;
+    if let Err(err) = stored {
+        // the client only reads the answer once its whole body went out
+        while let Ok(Some(mut field)) = data.next_field().await {
+            while let Ok(Some(_)) = field.chunk().await {}
+        }
+        return Err(err);
+    }
+    par_mp4_remux(processes, non_mp4_paths).await?;
+
+    Ok(())
+}
+
+async fn store_fields(
+    data: &mut Multipart,
+    shares: &Shares,
+    state_dir: &Path,
+    quotas: &Quotas,
+    user: &User,
+    mut length: Option<u64>,
+    non_mp4_paths: &mut Vec<PathBuf>,
+) -> ServerResult<()> {
+    let mut budget = quotas.budget(shares, state_dir, user.0.as_deref()).await;
     while let Some(mut field) = data.next_field().await? {
-        let name = field.name().unwrap();
-        let path = PathBuf::from_str(name).unwrap();
-        let path = target_dir.join(path);
-        let mut file = BufWriter::new(File::create(&path).await?);
-        while let Some(chunk) = field.chunk().await? {
-            file.write_all(&chunk).await?;
-            file.flush().await?;
+        let name = field
+            .name()
+            .ok_or_else(|| ServerError::MultiPart("unnamed field".to_string()))?;
+        let relative = PathBuf::from(name);
+        let path = shares.writable(&relative, user)?;
+        let share_dir = shares.share_dir(&relative)?;
+        let remaining = budget.remaining(&relative).await?;
+        // the whole request is known to be too big before anything is written
+        if let Some(length) = length.take() {
+            quota::check(&path, length.saturating_sub(MULTIPART_OVERHEAD), remaining)?;
         }
-        if path
+
+        let partial = partial_path(&path);
+        let written = match receive(&mut field, &partial, share_dir, quotas, remaining).await {
+            Ok(x) => x,
+            Err(err) => {
+                let _ = fs::remove_file(&partial).await;
+                return Err(match err {
+                    ServerError::InsufficientStorage(_, x) => {
+                        ServerError::InsufficientStorage(path, x)
+                    }
+                    err => err,
+                });
+            }
+        };
+        fs::rename(&partial, &path).await?;
+        budget.spend(&relative, written);
+
+        let remuxed = path
             .extension()
             .and_then(|x| x.to_str())
-            .is_some_and(|x| VIDEO_X.contains(&x) && x != "mp4")
-        {
+            .is_some_and(|x| VIDEO_X.contains(&x) && x != "mp4");
+        // what the user ends up with is the mp4 the upload is remuxed to
+        let stored = if remuxed {
+            relative.with_extension("mp4")
+        } else {
+            relative
+        };
+        quotas.record(state_dir, user.0.as_deref(), &stored).await;
+        if remuxed {
             non_mp4_paths.push(path);
         };
     }
-    par_mp4_remux(non_mp4_paths).await?;
-
     Ok(())
 }
 
+/// streams one uploaded file into `to`, stopping as soon as it outgrows its quota
+async fn receive(
+    field: &mut Field<'_>,
+    to: &Path,
+    share_dir: &Path,
+    quotas: &Quotas,
+    remaining: u64,
+) -> ServerResult<u64> {
+    let mut file = BufWriter::new(File::create(to).await?);
+    let (mut written, mut checked) = (0, 0);
+    while let Some(chunk) = field.chunk().await? {
+        written += chunk.len() as u64;
+        quota::check(to, written, remaining)?;
+        // others write to the same disk while this upload streams
+        if written - checked > FREE_SPACE_CHECK {
+            checked = written;
+            if quota::free_space(share_dir)? < quotas.min_free {
+                return Err(ServerError::InsufficientStorage(to.to_path_buf(), 0));
+            }
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(written)
+}
+
 pub async fn ls(
-    State(Context { target_dir }): State<Context>,
+    State(Context { shares, .. }): State<Context>,
+    user: User,
     Json(base): Json<PathBuf>,
 ) -> ServerResult<Json<Vec<Unit>>> {
-    let root = target_dir.join(base);
-    let mut dir = fs::read_dir(&root).await?;
     let mut paths = Vec::new();
+    // the named shares sit at the top, over whatever the root share holds
+    let top = base.as_os_str().is_empty();
+    if top {
+        paths.extend(shares.listed(&user).map(|x| Unit {
+            path: PathBuf::from(&x.name),
+            kind: UnitKind::Dirctory,
+        }));
+    }
+    let root = match shares.resolve(&base, &user) {
+        Ok(x) => x,
+        Err(ServerError::ShareNotFound(_)) if top => return Ok(Json(paths)),
+        Err(err) => return Err(err),
+    };
+    let mut dir = fs::read_dir(&root).await?;
     while let Some(x) = dir.next_entry().await? {
+        let path = base.join(x.file_name());
+        if top && paths.iter().any(|x| x.path == path) {
+            continue;
+        }
         let kind = if x.file_type().await?.is_dir() {
             UnitKind::Dirctory
         } else {
             UnitKind::File
         };
-        let unit = Unit {
-            path: x.path().strip_prefix(&target_dir)?.to_path_buf(),
-            kind,
-        };
+        let unit = Unit { path, kind };
         paths.push(unit);
     }
 
@@ -147,10 +280,11 @@ pub async fn ls(
 }
 
 pub async fn mkdir(
-    State(Context { target_dir }): State<Context>,
+    State(Context { shares, .. }): State<Context>,
+    user: User,
     Json(target): Json<PathBuf>,
 ) -> ServerResult<()> {
-    let target = target_dir.join(target);
+    let target = shares.writable(&target, &user)?;
     fs::create_dir(target).await?;
     Ok(())
 }
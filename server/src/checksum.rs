@@ -0,0 +1,287 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+    jobs::{Job, Progress},
+    shares::Shares,
+};
+use axum::{Json, extract::State};
+use common::{Checksums, FileChecksum, HashAlgorithm};
+use sha2::Digest;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Instant, SystemTime},
+};
+
+/// checksums kept at most, past this those of changed or removed files go, then the oldest
+const MAX_KNOWN: usize = 10_000;
+
+/// checksums computed already, they hold while their file keeps its size and modification time
+#[derive(Default)]
+pub(crate) struct Known(Mutex<HashMap<(PathBuf, HashAlgorithm), Hashed>>);
+
+struct Hashed {
+    len: u64,
+    modified: SystemTime,
+    hex: String,
+    at: Instant,
+}
+
+impl Hashed {
+    /// whether `path` still is what was hashed
+    fn holds(&self, path: &Path) -> bool {
+        unchanged(path, self.len, self.modified)
+    }
+}
+
+fn unchanged(path: &Path, len: u64, modified: SystemTime) -> bool {
+    fs::metadata(path).is_ok_and(|x| x.len() == len && x.modified().ok() == Some(modified))
+}
+
+impl Known {
+    fn get(&self, path: &Path, algorithm: HashAlgorithm) -> Option<String> {
+        let key = (path.to_path_buf(), algorithm);
+        let mut known = self.0.lock().unwrap();
+        if known.get(&key)?.holds(path) {
+            return known.get(&key).map(|x| x.hex.clone());
+        }
+        known.remove(&key);
+        None
+    }
+
+    /// the checksums whose file changed or went, with when they were taken
+    fn stale(&self) -> Vec<((PathBuf, HashAlgorithm), Instant)> {
+        let known = self.0.lock().unwrap();
+        let known = known
+            .iter()
+            .map(|(key, x)| (key.clone(), x.len, x.modified, x.at))
+            .collect::<Vec<_>>();
+        // the files are looked at once the lock is gone, every other lookup waits on it
+        known
+            .into_iter()
+            .filter(|((path, _), len, modified, _)| !unchanged(path, *len, *modified))
+            .map(|(key, _, _, at)| (key, at))
+            .collect()
+    }
+
+    fn insert(&self, path: &Path, algorithm: HashAlgorithm, hashed: Hashed) {
+        if self.0.lock().unwrap().len() >= MAX_KNOWN {
+            let stale = self.stale();
+            let mut known = self.0.lock().unwrap();
+            for (key, at) in stale {
+                // unless hashed again meanwhile
+                if known.get(&key).is_some_and(|x| x.at == at) {
+                    known.remove(&key);
+                }
+            }
+        }
+        let mut known = self.0.lock().unwrap();
+        if known.len() >= MAX_KNOWN {
+            let mut ages = known.values().map(|x| x.at).collect::<Vec<_>>();
+            ages.sort_unstable();
+            let cutoff = ages[MAX_KNOWN / 4];
+            known.retain(|_, x| x.at > cutoff);
+        }
+        known.insert((path.to_path_buf(), algorithm), hashed);
+    }
+
+    /// what is known of `path`, or its hash with the bytes read as progress of `job`
+    fn hash(&self, path: &Path, algorithm: HashAlgorithm, job: &Job) -> ServerResult<String> {
+        if let Some(hex) = self.get(path, algorithm) {
+            job.advance(fs::metadata(path)?.len());
+            return Ok(hex);
+        }
+        self.fresh(path, algorithm, job)
+    }
+
+    /// the hash of `path` read again whatever is known, which it then replaces
+    fn fresh(&self, path: &Path, algorithm: HashAlgorithm, job: &Job) -> ServerResult<String> {
+        // taken before reading, a file changed meanwhile is hashed again next time
+        let metadata = fs::metadata(path)?;
+        let hex = hash(Progress(File::open(path)?, Some(job)), algorithm)?;
+        let hashed = Hashed {
+            len: metadata.len(),
+            modified: metadata.modified()?,
+            hex: hex.clone(),
+            at: Instant::now(),
+        };
+        self.insert(path, algorithm, hashed);
+        Ok(hex)
+    }
+}
+
+fn hash(mut reader: impl Read, algorithm: HashAlgorithm) -> io::Result<String> {
+    match algorithm {
+        HashAlgorithm::Sha256 => {
+            let mut hasher = sha2::Sha256::new();
+            io::copy(&mut reader, &mut hasher)?;
+            Ok(format!("{:x}", hasher.finalize()))
+        }
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            io::copy(&mut reader, &mut hasher)?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+    }
+}
+
+/// `target` on disk, only files have checksums
+fn resolve_file(shares: &Shares, target: &Path, user: &User) -> ServerResult<PathBuf> {
+    let path = shares.resolve(target, user)?;
+    if !fs::metadata(&path)?.is_file() {
+        return Err(ServerError::NotAFile(target.to_path_buf()));
+    }
+    Ok(path)
+}
+
+fn total(files: &[PathBuf]) -> ServerResult<u64> {
+    files
+        .iter()
+        .map(|x| Ok(fs::metadata(x)?.len()))
+        .sum::<ServerResult<u64>>()
+}
+
+/// hashes the files in the background, the job id is returned right away
+pub async fn checksum(
+    State(Context {
+        shares,
+        jobs,
+        checksums,
+        ..
+    }): State<Context>,
+    user: User,
+    Json(Checksums { targets, algorithm }): Json<Checksums>,
+) -> ServerResult<Json<u64>> {
+    let files = targets
+        .iter()
+        .map(|x| resolve_file(&shares, x, &user))
+        .collect::<ServerResult<Vec<_>>>()?;
+    let name = match targets.as_slice() {
+        [target] => format!("{algorithm} of {}", target.display()),
+        _ => format!("{algorithm} of {} files", targets.len()),
+    };
+    let job = jobs.start(name, total(&files)?);
+    let id = job.id();
+
+    tokio::task::spawn_blocking(move || {
+        let result = files
+            .iter()
+            .try_for_each(|x| checksums.hash(x, algorithm, &job).map(|_| ()));
+        job.finish(result);
+    });
+    Ok(Json(id))
+}
+
+/// the checksums of `targets` computed already, by any job
+pub async fn known(
+    State(Context {
+        shares, checksums, ..
+    }): State<Context>,
+    user: User,
+    Json(targets): Json<Vec<PathBuf>>,
+) -> ServerResult<Json<Vec<FileChecksum>>> {
+    let mut known = Vec::new();
+    for target in targets {
+        let path = shares.resolve(&target, &user)?;
+        for algorithm in HashAlgorithm::ALL {
+            if let Some(hex) = checksums.get(&path, algorithm) {
+                known.push(FileChecksum {
+                    path: target.clone(),
+                    algorithm,
+                    hex,
+                });
+            }
+        }
+    }
+    Ok(Json(known))
+}
+
+/// hashes `targets` and the copies `cp` made of them in `to`, the job fails at the first that differs
+pub async fn verify(
+    State(Context {
+        shares,
+        jobs,
+        checksums,
+        ..
+    }): State<Context>,
+    user: User,
+    Json((targets, to)): Json<(Vec<PathBuf>, PathBuf)>,
+) -> ServerResult<Json<u64>> {
+    let mut pairs = Vec::new();
+    for target in &targets {
+        let source = resolve_file(&shares, target, &user)?;
+        let name = source.file_name().ok_or(ServerError::NonePathFilename)?;
+        let copy = to.join(name);
+        pairs.push((source, resolve_file(&shares, &copy, &user)?, copy));
+    }
+    let files = pairs
+        .iter()
+        .flat_map(|(source, copy, _)| [source.clone(), copy.clone()])
+        .collect::<Vec<_>>();
+    let job = jobs.start(format!("verify copies in {}", to.display()), total(&files)?);
+    let id = job.id();
+
+    tokio::task::spawn_blocking(move || {
+        let result = pairs.iter().try_for_each(|(source, copy, name)| {
+            // what is known may predate the copy, both are read again
+            let source = checksums.fresh(source, HashAlgorithm::Blake3, &job)?;
+            let copy = checksums.fresh(copy, HashAlgorithm::Blake3, &job)?;
+            if source != copy {
+                return Err(ServerError::ChecksumMismatch(name.clone()));
+            }
+            Ok(())
+        });
+        job.finish(result);
+    });
+    Ok(Json(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn known_digests() {
+        assert_eq!(
+            hash(&b"abc"[..], HashAlgorithm::Sha256).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash(&b""[..], HashAlgorithm::Blake3).unwrap(),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+    }
+
+    #[test]
+    fn stale_checksums_go() {
+        let dir = TempDir::new("known");
+        let path = dir.join("abc");
+        fs::write(&path, "abc").unwrap();
+        let job = crate::jobs::Jobs::default().start(String::new(), 0);
+        let known = Known::default();
+        let hex = known.hash(&path, HashAlgorithm::Blake3, &job).unwrap();
+        assert_eq!(known.get(&path, HashAlgorithm::Blake3), Some(hex));
+
+        fs::write(&path, "abcd").unwrap();
+        assert_eq!(known.get(&path, HashAlgorithm::Blake3), None);
+        assert!(known.0.lock().unwrap().is_empty());
+
+        // once full, what no longer holds makes room
+        for i in 0..MAX_KNOWN {
+            let hashed = Hashed {
+                len: 0,
+                modified: SystemTime::UNIX_EPOCH,
+                hex: String::new(),
+                at: Instant::now(),
+            };
+            known.insert(&path.join(i.to_string()), HashAlgorithm::Sha256, hashed);
+        }
+        known.hash(&path, HashAlgorithm::Blake3, &job).unwrap();
+        assert_eq!(known.0.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::{Context, processes::Processes, shares::Shares};
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use common::ServerEvent;
+use futures_util::{Stream, stream};
+use partitions::{HotplugEvent, Outcome};
+use std::{convert::Infallible, path::PathBuf, sync::Arc};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// forwards every [`ServerEvent`] as it happens
+pub async fn events(
+    State(Context { events, .. }): State<Context>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = stream::unfold(events.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(x) => {
+                    let event = Event::default().json_data(x).unwrap_or_default();
+                    return Some((Ok(event), receiver));
+                }
+                // a slow client misses some events rather than all of them
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// mounts plugged in drives under `target` and tells the clients about them, forever
+pub(crate) async fn hotplug(
+    target: PathBuf,
+    shares: Arc<Shares>,
+    policy: partitions::Policy,
+    processes: Processes,
+    events: broadcast::Sender<ServerEvent>,
+) {
+    let mut hotplug = partitions::watch(target, policy, move || {
+        let processes = processes.clone();
+        async move { processes.permit().await }
+    });
+    while let Some(event) = hotplug.recv().await {
+        let event = match event {
+            HotplugEvent::Added(result) => {
+                let at = match result.outcome {
+                    Outcome::Mounted(at) | Outcome::AlreadyMounted(at) => shares.virtual_path(&at),
+                    Outcome::Skipped(_) | Outcome::Failed(_) => None,
+                };
+                ServerEvent::DriveAdded {
+                    name: partitions::mount_name(&result.partition),
+                    at,
+                }
+            }
+            HotplugEvent::Removed(partition) => ServerEvent::DriveRemoved {
+                name: partitions::mount_name(&partition),
+            },
+        };
+        // nobody listening is fine
+        let _ = events.send(event);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+    jobs::{Job, Progress},
+    quota,
+};
+use axum::{Json, extract::State};
+use common::{ArchiveKind, Extraction, OutputNaming};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+/// entries all archives of one extraction may hold together
+const MAX_ENTRIES: u64 = 100_000;
+/// an archive may unpack to this many times its own size, more is a zip bomb
+const MAX_RATIO: u64 = 200;
+
+/// unpacks the archives in the background, the job id is returned right away
+pub async fn extract(
+    State(Context {
+        shares,
+        state_dir,
+        quotas,
+        jobs,
+        ..
+    }): State<Context>,
+    user: User,
+    Json(Extraction {
+        archives,
+        to,
+        naming,
+    }): Json<Extraction>,
+) -> ServerResult<Json<u64>> {
+    let dir = shares.writable(&to, &user)?;
+    let remaining = quotas
+        .remaining(&shares, &state_dir, &to, user.0.as_deref())
+        .await?;
+    let mut targets = Vec::new();
+    let mut total = 0;
+    for archive in &archives {
+        let path = shares.resolve(archive, &user)?;
+        let kind =
+            ArchiveKind::of(&path).ok_or_else(|| ServerError::NotAnArchive(archive.clone()))?;
+        total += tokio::fs::metadata(&path).await?.len();
+        targets.push((path, kind));
+    }
+    let name = match archives.as_slice() {
+        [archive] => format!("extract {}", archive.display()),
+        _ => format!("extract {} archives", archives.len()),
+    };
+    let job = jobs.start(name, total);
+    let id = job.id();
+
+    tokio::task::spawn_blocking(move || {
+        let mut budget = Budget {
+            bytes: remaining,
+            entries: MAX_ENTRIES,
+        };
+        let result = targets.iter().try_for_each(|(archive, kind)| {
+            unpack(archive, *kind, &dir, naming, &mut budget, &job)
+        });
+        job.finish(result);
+    });
+    Ok(Json(id))
+}
+
+/// what is left of what one extraction may write
+struct Budget {
+    bytes: u64,
+    entries: u64,
+}
+
+fn unpack(
+    archive: &Path,
+    kind: ArchiveKind,
+    to: &Path,
+    naming: OutputNaming,
+    budget: &mut Budget,
+    job: &Job,
+) -> ServerResult<()> {
+    let file = File::open(archive)?;
+    let len = file.metadata()?.len();
+    let mut unpacker = Unpacker {
+        archive,
+        to,
+        naming,
+        budget,
+        // small archives may hold some empty files
+        ratio_left: len.saturating_mul(MAX_RATIO).max(1024 * 1024),
+    };
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(file).map_err(invalid)?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i).map_err(invalid)?;
+                let name = PathBuf::from(entry.name().map_err(invalid)?.as_ref());
+                if entry.is_dir() {
+                    unpacker.dir(&name)?;
+                } else if !entry.is_symlink() {
+                    unpacker.file(&name, &mut entry)?;
+                }
+                job.advance(entry.compressed_size());
+            }
+            Ok(())
+        }
+        ArchiveKind::Tar => unpacker.tar(Progress(file, Some(job))),
+        ArchiveKind::TarGz => unpacker.tar(flate2::read::GzDecoder::new(Progress(file, Some(job)))),
+        ArchiveKind::TarZst => unpacker.tar(zstd::Decoder::new(Progress(file, Some(job)))?),
+    }
+}
+
+struct Unpacker<'a> {
+    archive: &'a Path,
+    to: &'a Path,
+    naming: OutputNaming,
+    budget: &'a mut Budget,
+    /// bytes this archive may still unpack to
+    ratio_left: u64,
+}
+
+impl Unpacker<'_> {
+    fn tar(&mut self, reader: impl Read) -> ServerResult<()> {
+        let mut tar = tar::Archive::new(reader);
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_path_buf();
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => self.dir(&name)?,
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    self.file(&name, &mut entry)?
+                }
+                // links could point anywhere, whatever else is not a plain file
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn count(&mut self) -> ServerResult<()> {
+        self.budget.entries = self
+            .budget
+            .entries
+            .checked_sub(1)
+            .ok_or_else(|| ServerError::ExtractLimit(self.archive.to_path_buf()))?;
+        Ok(())
+    }
+
+    fn dir(&mut self, name: &Path) -> ServerResult<()> {
+        // `./`, what `tar -C dir .` starts with, is `to` itself
+        if name.components().all(|x| x == Component::CurDir) {
+            return Ok(());
+        }
+        self.count()?;
+        fs::create_dir_all(inside(self.to, name)?)?;
+        Ok(())
+    }
+
+    fn file(&mut self, name: &Path, reader: &mut impl Read) -> ServerResult<()> {
+        self.count()?;
+        let path = output(inside(self.to, name)?, self.naming)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // what is overwritten gets replaced, writing through a symlink could land anywhere
+        if fs::symlink_metadata(&path).is_ok_and(|x| !x.is_dir()) {
+            fs::remove_file(&path)?;
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let mut buffer = vec![0; 64 * 1024];
+        let result = loop {
+            let n = match reader.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(n) => n as u64,
+                Err(err) => break Err(err.into()),
+            };
+            // sizes in headers can lie, what was written counts
+            if n > self.ratio_left {
+                break Err(ServerError::ExtractLimit(self.archive.to_path_buf()));
+            }
+            if let Err(err) = quota::check(&path, n, self.budget.bytes) {
+                break Err(err);
+            }
+            self.ratio_left -= n;
+            self.budget.bytes -= n;
+            if let Err(err) = file.write_all(&buffer[..n as usize]) {
+                break Err(err.into());
+            }
+        };
+        if result.is_err() {
+            drop(file);
+            let _ = fs::remove_file(&path);
+        }
+        result
+    }
+}
+
+/// `name` under `to`, unless it would climb out of it
+fn inside(to: &Path, name: &Path) -> ServerResult<PathBuf> {
+    let mut path = to.to_path_buf();
+    for component in name.components() {
+        match component {
+            Component::Normal(x) => path.push(x),
+            Component::CurDir => (),
+            _ => return Err(ServerError::Forbidden(name.to_path_buf())),
+        }
+    }
+    if path == to {
+        return Err(ServerError::Forbidden(name.to_path_buf()));
+    }
+    Ok(path)
+}
+
+/// where a file goes when `path` may be taken already
+pub(crate) fn output(path: PathBuf, naming: OutputNaming) -> ServerResult<PathBuf> {
+    if !path.exists() {
+        return Ok(path);
+    }
+    match naming {
+        OutputNaming::Overwrite if path.is_file() => Ok(path),
+        OutputNaming::Overwrite | OutputNaming::Keep => Err(ServerError::OutputExists(path)),
+        OutputNaming::Number => {
+            let name = path
+                .file_name()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default()
+                .to_string();
+            // `.tar.gz` and alike are one extension, a leading dot is none
+            let split = name
+                .find(".tar.")
+                .or_else(|| name.rfind('.'))
+                .filter(|x| *x > 0)
+                .unwrap_or(name.len());
+            let (stem, extension) = name.split_at(split);
+            (1..)
+                .map(|i| path.with_file_name(format!("{stem} ({i}){extension}")))
+                .find(|x| !x.exists())
+                .ok_or(ServerError::OutputExists(path))
+        }
+    }
+}
+
+fn invalid(err: zip::result::ZipError) -> ServerError {
+    ServerError::Io(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn taken_names_are_numbered() {
+        let dir = TempDir::new("output");
+        for name in ["a.txt", "b.tar.zst", ".hidden"] {
+            File::create(dir.join(name)).unwrap();
+        }
+        for (name, numbered) in [
+            ("a.txt", "a (1).txt"),
+            ("b.tar.zst", "b (1).tar.zst"),
+            (".hidden", ".hidden (1)"),
+        ] {
+            assert_eq!(
+                output(dir.join(name), OutputNaming::Number).unwrap(),
+                dir.join(numbered)
+            );
+        }
+        assert!(output(dir.join("a.txt"), OutputNaming::Keep).is_err());
+    }
+
+    #[test]
+    fn entries_stay_inside() {
+        let to = Path::new("/srv/out");
+        assert_eq!(
+            inside(to, Path::new("./a/b.txt")).unwrap(),
+            Path::new("/srv/out/a/b.txt")
+        );
+        for name in ["../evil", "/etc/passwd", "a/../../evil", "."] {
+            assert!(matches!(
+                inside(to, Path::new(name)),
+                Err(ServerError::Forbidden(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+    thumbnail::cache_name,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use common::HLS_PATH;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs,
+    process::{Child, Command},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+
+pub(crate) const PLAYLIST: &str = "index.m3u8";
+const SEGMENT_SECONDS: u32 = 6;
+/// transcodes nobody fetched a segment or playlist from for this long are killed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const REAP_INTERVAL: Duration = Duration::from_secs(15);
+const PLAYLIST_WAIT: Duration = Duration::from_secs(30);
+
+struct Transcode {
+    child: Child,
+    last_access: Instant,
+    /// frees the transcode slot once the transcode is forgotten
+    _permit: OwnedSemaphorePermit,
+}
+
+/// running ffmpeg hls transcodes keyed by their cache directory name
+///
+/// they run for as long as somebody watches, so they have slots of their own rather than
+/// holding the short lived processes up
+#[derive(Clone)]
+pub(crate) struct Transcodes {
+    running: Arc<Mutex<HashMap<String, Transcode>>>,
+    slots: Arc<Semaphore>,
+}
+
+impl Transcodes {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            running: Arc::default(),
+            slots: Arc::new(Semaphore::new(limit.max(1))),
+        }
+    }
+
+    fn touch(&self, key: &str) {
+        if let Some(x) = self.running.lock().unwrap().get_mut(key) {
+            x.last_access = Instant::now();
+        }
+    }
+
+    fn is_running(&self, key: &str) -> bool {
+        self.running
+            .lock()
+            .unwrap()
+            .get_mut(key)
+            .is_some_and(|x| matches!(x.child.try_wait(), Ok(None)))
+    }
+
+    /// exit status of a finished transcode, forgetting about it
+    fn finished(&self, key: &str) -> Option<bool> {
+        let mut transcodes = self.running.lock().unwrap();
+        let status = transcodes.get_mut(key)?.child.try_wait().ok()??;
+        transcodes.remove(key);
+        Some(status.success())
+    }
+
+    /// kills idle transcodes and forgets finished ones, forever
+    ///
+    /// what the killed ones wrote can not be resumed, their directories in `cache_dir` go too
+    pub(crate) async fn reap(self, cache_dir: PathBuf) {
+        let mut interval = tokio::time::interval(REAP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mut killed = Vec::new();
+            self.running.lock().unwrap().retain(|key, x| {
+                if !matches!(x.child.try_wait(), Ok(None)) {
+                    return false;
+                }
+                if x.last_access.elapsed() > IDLE_TIMEOUT {
+                    let _ = x.child.start_kill();
+                    killed.push(key.clone());
+                    return false;
+                }
+                true
+            });
+            for key in killed {
+                let _ = fs::remove_dir_all(cache_dir.join("hls").join(key)).await;
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HlsQuery {
+    path: PathBuf,
+}
+
+/// starts (or joins) a transcode of `path` and redirects to its playlist
+pub async fn hls(
+    State(Context {
+        shares,
+        cache_dir,
+        transcodes,
+        ..
+    }): State<Context>,
+    user: User,
+    Query(HlsQuery { path }): Query<HlsQuery>,
+) -> ServerResult<Redirect> {
+    let source = shares.resolve(&path, &user)?;
+    let modified = fs::metadata(&source).await?.modified()?;
+    let key = cache_name(&source, modified, "hls", "hls");
+    let dir = cache_dir.join("hls").join(&key);
+    let playlist = dir.join(PLAYLIST);
+
+    let complete = fs::read_to_string(&playlist)
+        .await
+        .is_ok_and(|x| x.contains("#EXT-X-ENDLIST"));
+    if !complete && !transcodes.is_running(&key) {
+        start(&transcodes, &key, &source, &dir).await?;
+    }
+
+    let started = Instant::now();
+    while !fs::try_exists(&playlist).await? {
+        if transcodes.finished(&key) == Some(false) || started.elapsed() > PLAYLIST_WAIT {
+            return Err(ServerError::FfmpagWait(source));
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+
+    Ok(Redirect::to(&format!("{HLS_PATH}/{key}/{PLAYLIST}")))
+}
+
+/// spawns the transcode of `source` into `dir` once a transcode slot is free
+async fn start(
+    transcodes: &Transcodes,
+    key: &str,
+    source: &std::path::Path,
+    dir: &std::path::Path,
+) -> ServerResult<()> {
+    // the semaphore is never closed
+    let permit = transcodes.slots.clone().acquire_owned().await.unwrap();
+    // someone else may have started it while this one waited for a slot
+    if transcodes.is_running(key) {
+        return Ok(());
+    }
+    // whatever is left is from a killed transcode, it can not be resumed
+    let _ = fs::remove_dir_all(dir).await;
+    fs::create_dir_all(dir).await?;
+    let playlist = dir.join(PLAYLIST);
+    let child = Command::new("ffmpeg")
+        .args(["-v", "error", "-i"])
+        .arg(source)
+        .args(["-map", "0:v:0?", "-map", "0:a:0?"])
+        .args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "23"])
+        .args(["-pix_fmt", "yuv420p"])
+        .args(["-c:a", "aac", "-b:a", "160k", "-ac", "2"])
+        .args(["-f", "hls", "-hls_playlist_type", "event"])
+        .args(["-hls_time", &SEGMENT_SECONDS.to_string()])
+        .arg("-hls_segment_filename")
+        .arg(dir.join("segment%05d.ts"))
+        .arg(&playlist)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|_| ServerError::FfmpagSpawn(source.to_path_buf()))?;
+    transcodes.running.lock().unwrap().insert(
+        key.to_string(),
+        Transcode {
+            child,
+            last_access: Instant::now(),
+            _permit: permit,
+        },
+    );
+    Ok(())
+}
+
+/// playlist and segments of a transcode started by [`hls`]
+pub async fn hls_file(
+    State(Context {
+        cache_dir,
+        transcodes,
+        ..
+    }): State<Context>,
+    Path((key, file)): Path<(String, String)>,
+) -> ServerResult<Response> {
+    let valid = |x: &str| !x.is_empty() && !x.contains(['/', '\\']) && !x.starts_with('.');
+    if !valid(&key) || !valid(&file) {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+    transcodes.touch(&key);
+    let Ok(bytes) = fs::read(cache_dir.join("hls").join(&key).join(&file)).await else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let (content_type, cache_control) = if file == PLAYLIST {
+        ("application/vnd.apple.mpegurl", "no-cache")
+    } else {
+        ("video/mp2t", "private, max-age=86400")
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        bytes,
+    )
+        .into_response())
+}
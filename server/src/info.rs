@@ -1,34 +1,116 @@
-use axum::Json;
+use axum::{Json, extract::State};
+use common::Settings;
 use serde::Serialize;
+use std::{
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
 
-use crate::ServerResult;
+use crate::{Context, ServerResult, auth::User, shares::Shares};
 
 #[derive(Serialize)]
 pub struct Disk {
     pub total_space: u64,
     pub available_space: u64,
     pub name: String,
+    /// where the filesystem shows up in the shared tree, empty for the one holding the root share
+    pub path: PathBuf,
+    pub file_system: String,
+    pub removable: bool,
+    pub read_only: bool,
+    /// (total, free), filesystems like vfat or btrfs have no fixed inode count
+    pub inodes: Option<(u64, u64)>,
 }
 
-pub async fn get_disks() -> ServerResult<Json<Vec<Disk>>> {
-    use std::path::Path;
-    fn from(value: &sysinfo::Disk) -> Disk {
-        Disk {
-            total_space: value.total_space(),
-            available_space: value.available_space(),
-            name: value
+fn inodes(mount_point: &Path) -> Option<(u64, u64)> {
+    let path = CString::new(mount_point.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 || stat.f_files == 0 {
+        return None;
+    }
+    Some((stat.f_files as u64, stat.f_ffree as u64))
+}
+
+/// what [`get_disks`] answers
+#[derive(Serialize)]
+pub struct Disks {
+    pub disks: Vec<Disk>,
+    /// bytes of every filesystem listed, counted once however many shares live on it
+    pub total_space: u64,
+    pub available_space: u64,
+}
+
+/// the filesystems reachable inside the shares, each followed by those mounted inside it
+pub async fn get_disks(
+    State(Context { shares, .. }): State<Context>,
+    user: User,
+) -> ServerResult<Json<Disks>> {
+    // sysinfo, canonicalize and statvfs all wait on the disks
+    let disks = tokio::task::spawn_blocking(move || disks(&shares, &user)).await?;
+    Ok(Json(disks))
+}
+
+fn disks(shares: &Shares, user: &User) -> Disks {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let mut result = Disks {
+        disks: Vec::new(),
+        total_space: 0,
+        available_space: 0,
+    };
+    let mut counted = Vec::new();
+
+    let visible = shares
+        .iter()
+        .filter(|x| shares.resolve(Path::new(&x.name), user).is_ok());
+    for share in visible {
+        let Ok(target) = share.path.canonicalize() else {
+            continue;
+        };
+        // the deepest mount point above the share is the filesystem it lives on
+        let holding = disks
+            .list()
+            .iter()
+            .filter(|x| target.starts_with(x.mount_point()))
+            .max_by_key(|x| x.mount_point().components().count());
+        let inside = disks
+            .list()
+            .iter()
+            .filter(|x| x.mount_point() != target && x.mount_point().starts_with(&target));
+
+        for x in holding.into_iter().chain(inside) {
+            // shares on the same filesystem are all listed, its space is only added up once
+            if !counted.contains(&x.mount_point()) {
+                counted.push(x.mount_point());
+                result.total_space += x.total_space();
+                result.available_space += x.available_space();
+            }
+            let inner = x
                 .mount_point()
-                .file_name()
-                .map(|x| x.to_str().unwrap().to_string())
-                .unwrap_or(String::from("root")),
+                .strip_prefix(&target)
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            result.disks.push(Disk {
+                total_space: x.total_space(),
+                available_space: x.available_space(),
+                name: inner
+                    .to_str()
+                    .filter(|x| !x.is_empty())
+                    .or(Some(share.name.as_str()).filter(|x| !x.is_empty()))
+                    .map(str::to_string)
+                    .or_else(|| Some(target.file_name()?.to_str()?.to_string()))
+                    .unwrap_or(String::from("root")),
+                path: Path::new(&share.name).join(inner),
+                file_system: x.file_system().to_string_lossy().to_string(),
+                removable: x.is_removable(),
+                read_only: x.is_read_only() || share.read_only,
+                inodes: inodes(x.mount_point()),
+            });
         }
     }
-    let boot = Path::new("/boot");
-    let disks = sysinfo::Disks::new_with_refreshed_list()
-        .list()
-        .iter()
-        .filter(|x| x.mount_point() != boot)
-        .map(from)
-        .collect();
-    Ok(Json(disks))
+    result
+}
+
+pub async fn settings(State(Context { settings, .. }): State<Context>) -> Json<Settings> {
+    Json(settings)
 }
@@ -0,0 +1,136 @@
+use crate::{Context, app_error::ServerResult};
+use axum::{Json, extract::State};
+use common::{JobInfo, JobState};
+use std::{
+    io::{self, Read},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// how long finished jobs stay listed, for the site to show how they ended
+const KEEP: Duration = Duration::from_secs(60);
+
+/// work running in the background, the site polls its progress
+#[derive(Clone, Default)]
+pub(crate) struct Jobs(Arc<Mutex<Listed>>);
+
+#[derive(Default)]
+struct Listed {
+    next: u64,
+    /// and when they finished
+    jobs: Vec<(JobInfo, Option<Instant>)>,
+}
+
+impl Jobs {
+    pub(crate) fn start(&self, name: String, total: u64) -> Job {
+        let mut listed = self.0.lock().unwrap();
+        let id = listed.next;
+        listed.next += 1;
+        let info = JobInfo {
+            id,
+            name,
+            done: 0,
+            total,
+            state: JobState::Running,
+        };
+        listed.jobs.push((info, None));
+        Job {
+            id,
+            jobs: self.clone(),
+        }
+    }
+
+    fn list(&self) -> Vec<JobInfo> {
+        let mut listed = self.0.lock().unwrap();
+        listed
+            .jobs
+            .retain(|(_, finished)| finished.is_none_or(|x| x.elapsed() < KEEP));
+        listed.jobs.iter().map(|(x, _)| x.clone()).collect()
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut JobInfo, &mut Option<Instant>)) {
+        let mut listed = self.0.lock().unwrap();
+        if let Some((info, finished)) = listed.jobs.iter_mut().find(|(x, _)| x.id == id) {
+            f(info, finished);
+        }
+    }
+}
+
+/// a running job, it failed if dropped before [`Job::finish`]
+pub(crate) struct Job {
+    id: u64,
+    jobs: Jobs,
+}
+
+impl Job {
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(crate) fn advance(&self, by: u64) {
+        self.jobs
+            .update(self.id, |x, _| x.done = x.done.saturating_add(by));
+    }
+
+    pub(crate) fn finish(self, result: ServerResult<()>) {
+        self.end(match result {
+            Ok(()) => JobState::Done,
+            Err(err) => JobState::Failed(err.to_string()),
+        });
+    }
+
+    fn end(&self, state: JobState) {
+        self.jobs.update(self.id, |x, finished| {
+            if x.state == JobState::Running {
+                x.state = state;
+                *finished = Some(Instant::now());
+            }
+        });
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        // a panic or an early return, nothing else leaves a job unfinished
+        self.end(JobState::Failed(String::from("stopped")));
+    }
+}
+
+/// counts what is read as progress of the job, if there is one
+pub(crate) struct Progress<'a, R>(pub(crate) R, pub(crate) Option<&'a Job>);
+
+impl<R: Read> Read for Progress<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.0.read(buf)?;
+        if let Some(job) = self.1 {
+            job.advance(n as u64);
+        }
+        Ok(n)
+    }
+}
+
+pub async fn jobs(State(Context { jobs, .. }): State<Context>) -> Json<Vec<JobInfo>> {
+    Json(jobs.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jobs_end_once() {
+        let jobs = Jobs::default();
+        let job = jobs.start(String::from("a"), 10);
+        job.advance(4);
+        assert_eq!(jobs.list()[0].percent(), 40);
+        job.finish(Ok(()));
+
+        let dropped = jobs.start(String::from("b"), 10);
+        drop(dropped);
+        let states = jobs.list().into_iter().map(|x| x.state).collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            [JobState::Done, JobState::Failed(String::from("stopped"))]
+        );
+    }
+}
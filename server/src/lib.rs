@@ -1,30 +1,110 @@
-use std::{net::SocketAddr, path::PathBuf, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
 
 use app_error::{ServerError, ServerResult};
 use axum::{
     Router,
     extract::DefaultBodyLimit,
-    routing::{get, post},
+    middleware,
+    routing::{get, get_service, post},
+};
+use common::{
+    ARCHIVE_PATH, CHECKSUM_PATH, CHECKSUMS_PATH, CONVERT_PATH, COVER_PATH, CP_PATH, DISKS_PATH,
+    EJECT_PATH, EVENTS_PATH, EXTRACT_PATH, HLS_PATH, JOBS_PATH, LINK_RM_PATH, LINKS_PATH, LS_PATH,
+    MKDIR_PATH, MOUNT_PATH, MP4_PATH, MUSIC_PATH, MV_PATH, PARTITIONS_PATH, POSTER_PATH,
+    PREVIEW_PATH, PROBE_PATH, PROCESSES_PATH, RM_PATH, SETTINGS_PATH, SHARES_PATH, SUBTITLE_PATH,
+    SUBTITLES_PATH, Settings, THUMBNAIL_PATH, UNMOUNT_PATH, UPLOAD_PATH, VERIFY_PATH,
 };
-use common::{CP_PATH, DISKS_PATH, LS_PATH, MKDIR_PATH, MP4_PATH, MV_PATH, RM_PATH, UPLOAD_PATH};
 use get_port::Ops;
-use tower_http::{cors::CorsLayer, services::ServeDir, timeout::TimeoutLayer};
+use tower_http::{
+    cors::CorsLayer,
+    services::{ServeDir, ServeFile},
+    timeout::TimeoutLayer,
+    trace::TraceLayer,
+};
 
 pub mod app_error;
+mod archive;
+pub use auth::{hash_password, is_password_hash};
+pub use quota::Quotas;
+pub use shares::Share;
+pub use tls::{Certificate, Tls};
+mod auth;
 mod cd;
+mod checksum;
+mod events;
+mod extract;
+mod hls;
 mod info;
+mod jobs;
+mod links;
+mod mounts;
 mod mp4;
+mod music;
+mod poster;
+mod probe;
+mod processes;
+mod quota;
+mod shares;
+mod stream;
+mod subtitle;
+#[cfg(test)]
+mod testing;
+mod thumbnail;
+mod tls;
 
 #[derive(Clone)]
 struct Context {
-    target_dir: PathBuf,
+    shares: std::sync::Arc<shares::Shares>,
+    /// where partitions get mounted
+    mount_dir: PathBuf,
+    cache_dir: PathBuf,
+    /// what must outlive the cache
+    state_dir: PathBuf,
+    transcodes: hls::Transcodes,
+    processes: processes::Processes,
+    policy: partitions::Policy,
+    /// users who may mount, unmount and eject, everyone when no users are configured
+    admins: std::sync::Arc<Option<HashSet<String>>>,
+    events: tokio::sync::broadcast::Sender<common::ServerEvent>,
+    quotas: std::sync::Arc<Quotas>,
+    settings: Settings,
+    links: std::sync::Arc<links::Links>,
+    jobs: jobs::Jobs,
+    checksums: std::sync::Arc<checksum::Known>,
+}
+
+/// `$XDG_STATE_HOME/webls`, `~/.local/state/webls` when it is not set
+fn default_state() -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|x| PathBuf::from(x).join(".local/state")))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("webls")
 }
 
 pub struct Server {
     site: PathBuf,
     target: PathBuf,
+    shares: Vec<Share>,
+    cache: PathBuf,
+    state: PathBuf,
+    bind: IpAddr,
     port: Option<u16>,
+    listener: Option<std::net::TcpListener>,
     timeout: Duration,
+    processes: usize,
+    policy: partitions::Policy,
+    hotplug: bool,
+    quotas: Quotas,
+    users: HashMap<String, String>,
+    admins: HashSet<String>,
+    read_only: bool,
+    tls: Option<Tls>,
 }
 
 impl Server {
@@ -32,52 +112,234 @@ impl Server {
         let port = get_port::tcp::TcpPort::any("0.0.0.0");
         Self {
             site,
+            shares: vec![Share::new(String::new(), target.clone())],
             target,
+            cache: std::env::temp_dir().join("webls"),
+            state: default_state(),
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
             port,
+            listener: None,
             timeout: Duration::from_secs(60 * 60),
+            // ffmpeg encoders are multithreaded on their own
+            processes: std::thread::available_parallelism().map_or(1, |x| x.get() / 2),
+            policy: partitions::Policy::default(),
+            hotplug: false,
+            quotas: Quotas::default(),
+            users: HashMap::new(),
+            admins: HashSet::new(),
+            read_only: false,
+            tls: None,
         }
     }
+    /// serves `share` under its name at the top of the tree, next to what the target holds
+    pub fn share(mut self, share: Share) -> Self {
+        self.shares.push(share);
+        self
+    }
+    /// replaces every share, the target included, partitions still get mounted under the target
+    pub fn shares(self, shares: Vec<Share>) -> Self {
+        Self { shares, ..self }
+    }
+    /// the address to listen on, every interface by default
+    pub fn bind(self, bind: IpAddr) -> Self {
+        Self { bind, ..self }
+    }
     pub fn port(self, port: u16) -> Self {
         Self {
             port: Some(port),
             ..self
         }
     }
+    /// serves on a listener bound beforehand, instead of binding the address and port
+    pub fn listener(self, listener: std::net::TcpListener) -> Self {
+        Self {
+            listener: Some(listener),
+            ..self
+        }
+    }
     pub fn timeout(self, timeout: Duration) -> Self {
         Self { timeout, ..self }
     }
+    pub fn cache(self, cache: PathBuf) -> Self {
+        Self { cache, ..self }
+    }
+    /// where what can not be rebuilt like the cache is kept, which uploads each user made
+    pub fn state(self, state: PathBuf) -> Self {
+        Self { state, ..self }
+    }
+    /// how many ffmpeg, ffprobe and mount processes may run at once, the rest wait their turn
+    ///
+    /// hls transcodes run for as long as somebody watches, as many of them get slots of their own
+    pub fn processes(self, processes: usize) -> Self {
+        Self { processes, ..self }
+    }
+    /// which partitions the web ui may mount, unmount and eject
+    pub fn mount_policy(self, policy: partitions::Policy) -> Self {
+        Self { policy, ..self }
+    }
+    /// mount drives plugged into the host under the target, as the mount policy allows
+    pub fn hotplug(self, hotplug: bool) -> Self {
+        Self { hotplug, ..self }
+    }
+    /// how much uploads may store, nothing but the disk size limits them by default
+    pub fn quotas(self, quotas: Quotas) -> Self {
+        Self { quotas, ..self }
+    }
+    /// user names and the [`hash_password`] hashes of the passwords that must be given with basic
+    /// auth, anyone may connect when empty
+    pub fn users(self, users: HashMap<String, String>) -> Self {
+        Self { users, ..self }
+    }
+    /// users who may mount, unmount and eject partitions, nobody when empty but everyone when no
+    /// [`users`](Self::users) are configured
+    pub fn admins(self, admins: HashSet<String>) -> Self {
+        Self { admins, ..self }
+    }
+    /// nothing can be uploaded, moved, deleted, converted or mounted, only browsed and downloaded
+    pub fn read_only(self, read_only: bool) -> Self {
+        Self { read_only, ..self }
+    }
+    /// serves https instead of http
+    pub fn tls(self, tls: Tls) -> Self {
+        Self {
+            tls: Some(tls),
+            ..self
+        }
+    }
     pub async fn serve(self) -> ServerResult<()> {
         let Self {
             site,
             target,
+            shares,
+            cache,
+            state,
+            bind,
             port,
+            listener,
             timeout,
+            processes,
+            policy,
+            hotplug,
+            quotas,
+            users,
+            admins,
+            read_only,
+            tls,
         } = self;
-        let Some(port) = port else {
-            return Err(ServerError::NonePort);
+        let listener = match (listener, port) {
+            (Some(listener), _) => listener,
+            (None, Some(port)) => std::net::TcpListener::bind(SocketAddr::new(bind, port))?,
+            (None, None) => return Err(ServerError::NonePort),
         };
-        let addr = SocketAddr::from(([0, 0, 0, 0], port));
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
 
         let site_dir = ServeDir::new(&site);
-        let target_dir = ServeDir::new(&target);
+        let processes = processes::Processes::new(processes);
+        let transcodes = hls::Transcodes::new(processes.limit());
+        tokio::spawn(transcodes.clone().reap(cache.clone()));
+        tokio::spawn(thumbnail::evict(cache.clone()));
+        let (events, _) = tokio::sync::broadcast::channel(16);
+        let shares = std::sync::Arc::new(shares::Shares::new(shares));
+        if hotplug {
+            tokio::spawn(events::hotplug(
+                target.clone(),
+                shares.clone(),
+                policy.clone(),
+                processes.clone(),
+                events.clone(),
+            ));
+        }
 
-        let app = Router::new()
+        let mutating = Router::new()
             .route(MP4_PATH, post(mp4::mp4_remux))
+            .route(CONVERT_PATH, post(mp4::convert))
             .route(UPLOAD_PATH, post(cd::upload))
             .route(CP_PATH, post(cd::cp))
             .route(MV_PATH, post(cd::mv))
             .route(RM_PATH, post(cd::rm))
-            .route(LS_PATH, post(cd::ls))
             .route(MKDIR_PATH, post(cd::mkdir))
+            .route(MOUNT_PATH, post(mounts::mount))
+            .route(UNMOUNT_PATH, post(mounts::unmount))
+            .route(EJECT_PATH, post(mounts::eject))
+            .route(EXTRACT_PATH, post(extract::extract))
+            .route(ARCHIVE_PATH, post(archive::create))
+            .route(LINKS_PATH, post(links::create))
+            .route(LINK_RM_PATH, post(links::revoke));
+        let app = Router::new()
+            .route(LS_PATH, post(cd::ls))
             .route(DISKS_PATH, get(info::get_disks))
-            .nest_service("/download", target_dir)
+            .route(SETTINGS_PATH, get(info::settings))
+            .route(PARTITIONS_PATH, get(mounts::list))
+            .route(THUMBNAIL_PATH, get(thumbnail::thumbnail))
+            .route(POSTER_PATH, get(poster::poster))
+            .route(PREVIEW_PATH, get(poster::preview))
+            .route(PROBE_PATH, post(probe::probe))
+            .route(MUSIC_PATH, post(music::music))
+            .route(COVER_PATH, get(poster::cover))
+            .route(SUBTITLES_PATH, get(subtitle::subtitles))
+            .route(SUBTITLE_PATH, get(subtitle::subtitle))
+            .route(EVENTS_PATH, get(events::events))
+            .route(SHARES_PATH, get(shares::shares))
+            .route(LINKS_PATH, get(links::list))
+            .route(JOBS_PATH, get(jobs::jobs))
+            .route(CHECKSUM_PATH, post(checksum::checksum))
+            .route(CHECKSUMS_PATH, post(checksum::known))
+            .route(VERIFY_PATH, post(checksum::verify))
+            .route(PROCESSES_PATH, get(processes::processes))
+            .route(HLS_PATH, get(hls::hls))
+            .route(&format!("{HLS_PATH}/{{key}}/{{file}}"), get(hls::hls_file))
+            .route("/download/{*path}", get(stream::download))
+            .route(ARCHIVE_PATH, get(archive::archive));
+        // a read only server does not even know the routes that change anything
+        let app = if read_only { app } else { app.merge(mutating) };
+        let links = std::sync::Arc::new(links::Links::load(&cache));
+        // without users nobody logs in, whoever runs it alone is the admin
+        let admins = (!users.is_empty()).then_some(admins);
+        let app = app
+            .route("/", get_service(ServeFile::new(site.join("index.html"))))
+            .layer(middleware::from_fn_with_state(
+                std::sync::Arc::new(auth::Users::new(users)),
+                auth::basic_auth,
+            ))
+            // share links and what the site loads need no login, the page and the api do
+            .merge(links::public(&site))
             .fallback_service(site_dir)
-            .with_state(Context { target_dir: target })
+            .with_state(Context {
+                shares,
+                mount_dir: target,
+                cache_dir: cache,
+                state_dir: state,
+                transcodes,
+                processes,
+                policy,
+                admins: std::sync::Arc::new(admins),
+                events,
+                quotas: std::sync::Arc::new(quotas),
+                settings: Settings { read_only },
+                links,
+                jobs: jobs::Jobs::default(),
+                checksums: Default::default(),
+            })
             .layer(TimeoutLayer::new(timeout))
             .layer(CorsLayer::permissive())
-            .layer(DefaultBodyLimit::disable());
+            .layer(DefaultBodyLimit::disable())
+            .layer(TraceLayer::new_for_http());
 
-        let listener = tokio::net::TcpListener::bind(&addr).await?;
+        if let Some(tls) = tls {
+            let certificate = tls.load()?;
+            tracing::info!(
+                "listening on https://{addr}, certificate sha-256 {}",
+                certificate.fingerprint()?
+            );
+            let config = certificate.config().await?;
+            axum_server::from_tcp_rustls(listener, config)
+                .serve(app.into_make_service())
+                .await?;
+            return Ok(());
+        }
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        tracing::info!("listening on http://{addr}");
         axum::serve(listener, app).await?;
         Ok(())
     }
@@ -0,0 +1,400 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    archive,
+    auth::{self, User},
+    stream,
+};
+use axum::{
+    Json, Router,
+    extract::{self, RawQuery, State},
+    http::{HeaderMap, HeaderValue, header},
+    response::{IntoResponse, Response},
+    routing::{get, get_service, post},
+};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use common::{
+    ARCHIVE_PATH, LINK_INFO_PATH, LINK_PREFIX, LS_PATH, LinkInfo, NewLink, PublicLink,
+    SETTINGS_PATH, SHARES_PATH, Settings, ShareInfo, UNLOCK_PATH, Unit, UnitKind,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::fs;
+use tower_http::services::ServeFile;
+
+const FILE: &str = "links.json";
+/// signs the cookies of unlocked links, kept next to them so they outlive restarts too
+const KEY_FILE: &str = "links.key";
+const COOKIE: &str = "webls_link";
+
+/// a file or directory anyone holding the token may browse and download, for a while
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Link {
+    token: String,
+    /// where the link points to, as clients call it
+    path: PathBuf,
+    /// unix seconds
+    expires: u64,
+    /// [`auth::hash_password`] of the password
+    password: Option<String>,
+    max_downloads: Option<u32>,
+    downloads: u32,
+}
+
+impl Link {
+    fn alive(&self, now: u64) -> bool {
+        now < self.expires && self.max_downloads.is_none_or(|x| self.downloads < x)
+    }
+
+    fn info(&self) -> LinkInfo {
+        LinkInfo {
+            token: self.token.clone(),
+            path: self.path.clone(),
+            expires: self.expires,
+            password: self.password.is_some(),
+            max_downloads: self.max_downloads,
+            downloads: self.downloads,
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs())
+}
+
+/// every share link, kept in the cache so they outlive restarts
+pub(crate) struct Links {
+    file: PathBuf,
+    links: Mutex<Vec<Link>>,
+    key: [u8; 32],
+}
+
+impl Links {
+    pub(crate) fn load(cache_dir: &Path) -> Self {
+        let file = cache_dir.join(FILE);
+        let links = std::fs::read(&file)
+            .ok()
+            .and_then(|x| serde_json::from_slice(&x).ok())
+            .unwrap_or_default();
+        Self {
+            file,
+            links: Mutex::new(links),
+            key: load_key(&cache_dir.join(KEY_FILE)),
+        }
+    }
+
+    /// what the cookie of `token` holds once unlocked, only this server can make it
+    fn session(&self, token: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("hmac takes any key");
+        mac.update(token.as_bytes());
+        mac
+    }
+
+    /// whether the cookie [`unlock`] sets came with the request
+    fn unlocked(&self, link: &Link, headers: &HeaderMap) -> bool {
+        if link.password.is_none() {
+            return true;
+        }
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|x| x.to_str().ok())
+            .flat_map(|x| x.split(';'))
+            .filter_map(|x| x.trim().split_once('='))
+            .filter(|(name, _)| *name == COOKIE)
+            .filter_map(|(_, value)| BASE64_URL_SAFE_NO_PAD.decode(value).ok())
+            .any(|x| self.session(&link.token).verify_slice(&x).is_ok())
+    }
+
+    /// runs `f` on the links still alive and writes them back
+    fn update<T>(&self, f: impl FnOnce(&mut Vec<Link>) -> ServerResult<T>) -> ServerResult<T> {
+        let mut links = self.links.lock().unwrap();
+        links.retain(|x| now() < x.expires);
+        let result = f(&mut links)?;
+        self.save(&links)?;
+        Ok(result)
+    }
+
+    /// runs `f` on the links still alive, they are only written back when some expired
+    fn read<T>(&self, f: impl FnOnce(&[Link]) -> T) -> ServerResult<T> {
+        let mut links = self.links.lock().unwrap();
+        let count = links.len();
+        links.retain(|x| now() < x.expires);
+        if links.len() != count {
+            self.save(&links)?;
+        }
+        Ok(f(&links))
+    }
+
+    fn save(&self, links: &[Link]) -> ServerResult<()> {
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.file, serde_json::to_vec(links).unwrap_or_default())?;
+        Ok(())
+    }
+
+    fn get(&self, token: &str) -> ServerResult<Link> {
+        let now = now();
+        self.links
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|x| x.token == token && x.alive(now))
+            .cloned()
+            .ok_or_else(|| ServerError::LinkNotFound(token.to_string()))
+    }
+
+    /// counts a download, unless the link ran out of them in the meantime
+    fn download(&self, token: &str) -> ServerResult<()> {
+        self.update(|links| {
+            let now = now();
+            let link = links
+                .iter_mut()
+                .find(|x| x.token == token && x.alive(now))
+                .ok_or_else(|| ServerError::LinkNotFound(token.to_string()))?;
+            link.downloads += 1;
+            Ok(())
+        })
+    }
+}
+
+/// the key in `file`, or a new one written there
+fn load_key(file: &Path) -> [u8; 32] {
+    if let Some(key) = std::fs::read(file).ok().and_then(|x| x.try_into().ok()) {
+        return key;
+    }
+    let mut key = [0; 32];
+    getrandom::fill(&mut key).expect("no random source for the link key");
+    if let Some(dir) = file.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(err) = std::fs::write(file, key) {
+        tracing::warn!("unlocked links lock again on restart, can not keep their key : {err}");
+    }
+    key
+}
+
+/// what the link points to on disk, and `path` inside it when it is a directory
+async fn locate(
+    Context { shares, links, .. }: &Context,
+    token: &str,
+    path: &Path,
+    headers: &HeaderMap,
+) -> ServerResult<(Link, PathBuf)> {
+    let link = links.get(token)?;
+    if !links.unlocked(&link, headers) {
+        return Err(ServerError::Locked(token.to_string()));
+    }
+    // nothing may climb out of what was shared
+    if !path.components().all(|x| matches!(x, Component::Normal(_))) {
+        return Err(ServerError::Forbidden(path.to_path_buf()));
+    }
+    let root = shares.locate(&link.path)?;
+    if fs::metadata(&root).await?.is_dir() {
+        return Ok((link, root.join(path)));
+    }
+    // a shared file is all there is, under its own name
+    match root.file_name() {
+        Some(name) if path == Path::new(name) => Ok((link, root)),
+        _ => Err(ServerError::Forbidden(path.to_path_buf())),
+    }
+}
+
+/// the links to what the caller may see
+pub async fn list(
+    State(Context { shares, links, .. }): State<Context>,
+    user: User,
+) -> ServerResult<Json<Vec<LinkInfo>>> {
+    links.read(|links| {
+        Json(
+            links
+                .iter()
+                .filter(|x| shares.resolve(&x.path, &user).is_ok())
+                .map(Link::info)
+                .collect(),
+        )
+    })
+}
+
+pub async fn create(
+    State(Context { shares, links, .. }): State<Context>,
+    user: User,
+    Json(new): Json<NewLink>,
+) -> ServerResult<Json<LinkInfo>> {
+    fs::metadata(shares.resolve(&new.path, &user)?).await?;
+    let mut token = [0u8; 16];
+    getrandom::fill(&mut token).map_err(|x| ServerError::Io(x.to_string()))?;
+    let token = BASE64_URL_SAFE_NO_PAD.encode(token);
+    let password = match new.password.filter(|x| !x.is_empty()) {
+        Some(x) => Some(tokio::task::spawn_blocking(move || auth::hash_password(&x)).await?),
+        None => None,
+    };
+    let link = Link {
+        password,
+        token,
+        path: new.path,
+        expires: now().saturating_add(new.expires_in),
+        max_downloads: new.max_downloads,
+        downloads: 0,
+    };
+    let info = link.info();
+    links.update(|links| {
+        links.push(link);
+        Ok(())
+    })?;
+    Ok(Json(info))
+}
+
+pub async fn revoke(
+    State(Context { shares, links, .. }): State<Context>,
+    user: User,
+    Json(token): Json<String>,
+) -> ServerResult<()> {
+    links.update(|links| {
+        let i = links
+            .iter()
+            .position(|x| x.token == token)
+            .ok_or_else(|| ServerError::LinkNotFound(token.clone()))?;
+        shares.resolve(&links[i].path, &user)?;
+        links.remove(i);
+        Ok(())
+    })
+}
+
+/// the routes anyone holding a link may use, without logging in
+pub(crate) fn public(site: &Path) -> Router<Context> {
+    let at = |x: &str| format!("{LINK_PREFIX}/{{token}}{x}");
+    Router::new()
+        .route(
+            &at(""),
+            get_service(ServeFile::new(site.join("index.html"))),
+        )
+        .route(&at(LINK_INFO_PATH), get(info))
+        .route(&at(UNLOCK_PATH), post(unlock))
+        .route(&at(LS_PATH), post(ls))
+        .route(&at("/download/{*path}"), get(download))
+        .route(&at(ARCHIVE_PATH), get(download_archive))
+        // the site asks for these like it does everywhere else
+        .route(&at(SETTINGS_PATH), get(settings))
+        .route(
+            &at(SHARES_PATH),
+            get(|| async { Json(Vec::<ShareInfo>::new()) }),
+        )
+}
+
+async fn info(
+    State(context): State<Context>,
+    extract::Path(token): extract::Path<String>,
+    headers: HeaderMap,
+) -> ServerResult<Json<PublicLink>> {
+    let link = context.links.get(&token)?;
+    let path = context.shares.locate(&link.path)?;
+    Ok(Json(PublicLink {
+        name: path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default()
+            .to_string(),
+        dir: fs::metadata(&path).await?.is_dir(),
+        locked: !context.links.unlocked(&link, &headers),
+        expires: link.expires,
+    }))
+}
+
+/// sets the cookie the rest of the link routes want, when the password is right
+async fn unlock(
+    State(Context { links, .. }): State<Context>,
+    extract::Path(token): extract::Path<String>,
+    Json(password): Json<String>,
+) -> ServerResult<Response> {
+    let link = links.get(&token)?;
+    if let Some(hash) = link.password.clone()
+        && !tokio::task::spawn_blocking(move || auth::verify_password(&hash, &password)).await?
+    {
+        return Err(ServerError::Locked(token));
+    }
+    let session = BASE64_URL_SAFE_NO_PAD.encode(links.session(&token).finalize().into_bytes());
+    let cookie = format!(
+        "{COOKIE}={session}; Path={LINK_PREFIX}/{token}; Max-Age={}; HttpOnly; SameSite=Strict",
+        link.expires.saturating_sub(now())
+    );
+    let cookie = HeaderValue::from_str(&cookie).map_err(|x| ServerError::Io(x.to_string()))?;
+    Ok(([(header::SET_COOKIE, cookie)], ()).into_response())
+}
+
+async fn ls(
+    State(context): State<Context>,
+    extract::Path(token): extract::Path<String>,
+    headers: HeaderMap,
+    Json(base): Json<PathBuf>,
+) -> ServerResult<Json<Vec<Unit>>> {
+    let root = context.shares.locate(&context.links.get(&token)?.path)?;
+    if !fs::metadata(&root).await?.is_dir() {
+        // a shared file is listed alone, under its own name
+        let name = PathBuf::from(root.file_name().unwrap_or_default());
+        if !base.as_os_str().is_empty() {
+            return Err(ServerError::Forbidden(base));
+        }
+        locate(&context, &token, &name, &headers).await?;
+        let kind = UnitKind::File;
+        return Ok(Json(vec![Unit { path: name, kind }]));
+    }
+    let (_, dir) = locate(&context, &token, &base, &headers).await?;
+    let mut units = Vec::new();
+    let mut entries = fs::read_dir(&dir).await?;
+    while let Some(x) = entries.next_entry().await? {
+        let kind = if x.file_type().await?.is_dir() {
+            UnitKind::Dirctory
+        } else {
+            UnitKind::File
+        };
+        units.push(Unit {
+            path: base.join(x.file_name()),
+            kind,
+        });
+    }
+    Ok(Json(units))
+}
+
+/// counted whenever the file is sent from its start, resumed and seeked ones are not counted again
+async fn download(
+    State(context): State<Context>,
+    extract::Path((token, path)): extract::Path<(String, PathBuf)>,
+    headers: HeaderMap,
+) -> ServerResult<Response> {
+    let (_, path) = locate(&context, &token, &path, &headers).await?;
+    let metadata = fs::metadata(&path).await?;
+    if metadata.is_file() && stream::sends_start(&metadata, &headers) {
+        context.links.download(&token)?;
+    }
+    stream::file(&path, &headers).await
+}
+
+/// counted as one download, whatever it holds
+async fn download_archive(
+    State(context): State<Context>,
+    extract::Path(token): extract::Path<String>,
+    headers: HeaderMap,
+    RawQuery(raw): RawQuery,
+) -> ServerResult<Response> {
+    let (format, paths) = archive::query(raw.as_deref());
+    let mut targets = Vec::new();
+    for path in paths {
+        targets.push(locate(&context, &token, &path, &headers).await?.1);
+    }
+    context.links.download(&token)?;
+    Ok(archive::stream(format, targets))
+}
+
+async fn settings() -> Json<Settings> {
+    Json(Settings { read_only: true })
+}
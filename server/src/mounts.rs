@@ -0,0 +1,141 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+};
+use axum::{Json, extract::State};
+use partitions::Partition;
+use std::path::Path;
+
+/// every filesystem attached to the host, mounted or not
+pub async fn list(
+    State(Context { processes, .. }): State<Context>,
+) -> ServerResult<Json<Vec<Partition>>> {
+    let _permit = processes.permit().await;
+    Ok(Json(partitions::partitions().await?))
+}
+
+async fn find(name: &str) -> ServerResult<Partition> {
+    partitions::partitions()
+        .await?
+        .into_iter()
+        .find(|x| x.name == name)
+        .ok_or_else(|| ServerError::PartitionNotFound(name.to_string()))
+}
+
+/// fails when `partition` is mounted anywhere but under `mount_dir`, what the host mounted itself
+/// stays mounted
+async fn mounted_by_us(partition: &Partition, mount_dir: &Path) -> ServerResult<()> {
+    let mount_dir = tokio::fs::canonicalize(mount_dir).await?;
+    match partition
+        .mountpoints
+        .iter()
+        .find(|x| !x.starts_with(&mount_dir))
+    {
+        Some(at) => Err(ServerError::Forbidden(at.clone())),
+        None => Ok(()),
+    }
+}
+
+/// mounts the partition `name` under the mount directory, as the mount policy allows
+pub async fn mount(
+    State(Context {
+        mount_dir,
+        processes,
+        policy,
+        admins,
+        ..
+    }): State<Context>,
+    user: User,
+    Json(name): Json<String>,
+) -> ServerResult<()> {
+    user.admin(&admins)?;
+    let _permit = processes.permit().await;
+    let partition = find(&name).await?;
+    policy
+        .check(&partition)
+        .map_err(ServerError::PartitionSkipped)?;
+    if !partition.is_mounted() {
+        partitions::mount(&partition, &mount_dir, policy.read_only).await?;
+    }
+    Ok(())
+}
+
+pub async fn unmount(
+    State(Context {
+        mount_dir,
+        processes,
+        policy,
+        admins,
+        ..
+    }): State<Context>,
+    user: User,
+    Json(name): Json<String>,
+) -> ServerResult<()> {
+    user.admin(&admins)?;
+    let _permit = processes.permit().await;
+    let partition = find(&name).await?;
+    // the host's own filesystems are never ours to unmount
+    policy
+        .check(&partition)
+        .map_err(ServerError::PartitionSkipped)?;
+    mounted_by_us(&partition, &mount_dir).await?;
+    partitions::unmount(&partition).await?;
+    Ok(())
+}
+
+/// syncs, unmounts and powers off a removable drive so it can be pulled out
+pub async fn eject(
+    State(Context {
+        mount_dir,
+        processes,
+        policy,
+        admins,
+        ..
+    }): State<Context>,
+    user: User,
+    Json(name): Json<String>,
+) -> ServerResult<()> {
+    user.admin(&admins)?;
+    let _permit = processes.permit().await;
+    let partition = find(&name).await?;
+    policy
+        .check(&partition)
+        .map_err(ServerError::PartitionSkipped)?;
+    if !partition.removable {
+        return Err(ServerError::NotRemovable(name));
+    }
+    mounted_by_us(&partition, &mount_dir).await?;
+    partitions::eject(&partition).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_what_we_mounted_is_unmounted() {
+        let mount_dir = std::env::temp_dir();
+        let mut partition = Partition {
+            name: "sdb1".into(),
+            dev_path: "/dev/sdb1".into(),
+            size: 0,
+            fstype: "ext4".into(),
+            label: None,
+            uuid: None,
+            mountpoints: Vec::new(),
+            removable: true,
+        };
+        assert!(mounted_by_us(&partition, &mount_dir).await.is_ok());
+
+        partition.mountpoints = vec![mount_dir.canonicalize().unwrap().join("sdb1")];
+        assert!(mounted_by_us(&partition, &mount_dir).await.is_ok());
+
+        partition.mountpoints.push("/media/sdb1".into());
+        assert!(matches!(
+            mounted_by_us(&partition, &mount_dir).await,
+            Err(ServerError::Forbidden(_))
+        ));
+    }
+}
@@ -1,49 +1,183 @@
 use super::Context;
-use crate::app_error::ServerResult;
+use crate::{
+    app_error::{ServerError, ServerResult},
+    auth::User,
+    processes::Processes,
+    thumbnail::partial_path,
+};
 use axum::{Json, extract::State};
-use std::path::PathBuf;
+use common::{Conversion, ConvertProfile, OutputNaming};
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::{fs, process::Command};
+
+/// how much of ffmpeg's stderr is kept in a failure
+const STDERR_TAIL: usize = 2048;
 
 pub async fn mp4_remux(
-    State(Context { target_dir }): State<Context>,
+    State(Context {
+        shares, processes, ..
+    }): State<Context>,
+    user: User,
     Json(targets): Json<Vec<PathBuf>>,
 ) -> ServerResult<()> {
-    par_mp4_remux(
-        targets
-            .into_iter()
-            .map(|target| target_dir.join(target))
-            .collect(),
-    )
-    .await?;
+    let targets = targets
+        .iter()
+        .map(|target| shares.writable(target, &user))
+        .collect::<ServerResult<_>>()?;
+    par_mp4_remux(processes, targets).await?;
 
     Ok(())
 }
 
-pub async fn par_mp4_remux(targets: Vec<PathBuf>) -> ServerResult<()> {
+pub async fn convert(
+    State(Context {
+        shares, processes, ..
+    }): State<Context>,
+    user: User,
+    Json(Conversion {
+        targets,
+        profile,
+        naming,
+        keep_source,
+    }): Json<Conversion>,
+) -> ServerResult<()> {
+    let targets = targets
+        .iter()
+        .map(|target| shares.writable(target, &user))
+        .collect::<ServerResult<_>>()?;
+    par_convert(processes, targets, profile, naming, keep_source).await
+}
+
+/// the lossy but always playable conversion uploads and the mp4 tool have always done
+pub(crate) async fn par_mp4_remux(processes: Processes, targets: Vec<PathBuf>) -> ServerResult<()> {
+    par_convert(
+        processes,
+        targets,
+        ConvertProfile::Mp4,
+        OutputNaming::Overwrite,
+        false,
+    )
+    .await
+}
+
+/// converts every target, no more at once than `processes` allows
+pub(crate) async fn par_convert(
+    processes: Processes,
+    targets: Vec<PathBuf>,
+    profile: ConvertProfile,
+    naming: OutputNaming,
+    keep_source: bool,
+) -> ServerResult<()> {
     use tokio::task::JoinSet;
     let mut set = JoinSet::new();
-    targets.into_iter().map(any_to_mp4).for_each(|x| {
-        set.spawn(x);
-    });
+    targets
+        .into_iter()
+        .map(|from| convert_one(processes.clone(), from, profile, naming, keep_source))
+        .for_each(|x| {
+            set.spawn(x);
+        });
 
+    // every conversion gets to finish, the first failure is reported
+    let mut result = Ok(());
     while let Some(x) = set.join_next().await {
-        x??;
+        if let (Ok(()), Err(err)) = (&result, x?) {
+            result = Err(err);
+        }
     }
-    Ok(())
+    result
 }
 
-pub async fn any_to_mp4(from: PathBuf) -> ServerResult<()> {
-    use tokio::{fs::remove_file, process::Command};
-    let mut to = from.clone();
-    to.set_extension("mp4");
-    let _ = remove_file(to.clone()).await;
-    Command::new("ffmpeg")
-        .arg("-i")
-        .arg(from.clone())
-        .arg(to)
-        .spawn()?
-        .wait()
-        .await?;
-
-    let _ = remove_file(from).await;
+async fn convert_one(
+    processes: Processes,
+    from: PathBuf,
+    profile: ConvertProfile,
+    naming: OutputNaming,
+    keep_source: bool,
+) -> ServerResult<()> {
+    let _permit = processes.permit().await;
+    let to = output_path(&from, profile, naming).await?;
+    let partial = partial_path(&to);
+
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-y", "-i"])
+        .arg(&from)
+        .args(profile_args(profile))
+        .arg(&partial)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|_| ServerError::FfmpagSpawn(from.clone()))?;
+
+    if !output.status.success() {
+        let _ = fs::remove_file(&partial).await;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail = stderr
+            .char_indices()
+            .rev()
+            .nth(STDERR_TAIL)
+            .map_or(&*stderr, |(i, _)| &stderr[i..]);
+        return Err(ServerError::Ffmpeg(from, tail.trim().to_string()));
+    }
+
+    fs::rename(&partial, &to).await?;
+    if !keep_source && from != to {
+        fs::remove_file(from).await?;
+    }
     Ok(())
 }
+
+#[rustfmt::skip]
+fn profile_args(profile: ConvertProfile) -> &'static [&'static str] {
+    match profile {
+        ConvertProfile::Remux => &["-map", "0:v?", "-map", "0:a?", "-c", "copy"],
+        ConvertProfile::Mp4 => &[
+            "-c:v", "libx264", "-pix_fmt", "yuv420p",
+            "-c:a", "aac",
+            "-movflags", "+faststart",
+        ],
+        ConvertProfile::H264Hd720 => &[
+            "-map", "0:v:0?", "-map", "0:a:0?",
+            "-vf", "scale=-2:'min(720,ih)'",
+            "-c:v", "libx264", "-preset", "medium", "-crf", "23", "-pix_fmt", "yuv420p",
+            "-c:a", "aac", "-b:a", "160k",
+            "-movflags", "+faststart",
+        ],
+        ConvertProfile::Mp3 => &["-vn", "-c:a", "libmp3lame", "-q:a", "2"],
+        ConvertProfile::Opus => &["-vn", "-c:a", "libopus", "-b:a", "128k"],
+    }
+}
+
+async fn output_path(
+    from: &Path,
+    profile: ConvertProfile,
+    naming: OutputNaming,
+) -> ServerResult<PathBuf> {
+    let to = from.with_extension(profile.extension());
+    // the source counts as taken, only overwriting may replace it
+    let taken = |x: PathBuf| async move { x == from || fs::try_exists(&x).await.unwrap_or(true) };
+    if !taken(to.clone()).await {
+        return Ok(to);
+    }
+    match naming {
+        // ffmpeg writes a partial file first, so even the source itself can be replaced
+        OutputNaming::Overwrite => Ok(to),
+        OutputNaming::Keep => Err(ServerError::OutputExists(to)),
+        OutputNaming::Number => {
+            let stem = to
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default()
+                .to_string();
+            for i in 1.. {
+                let numbered = to.with_file_name(format!("{stem} ({i}).{}", profile.extension()));
+                if !taken(numbered.clone()).await {
+                    return Ok(numbered);
+                }
+            }
+            unreachable!()
+        }
+    }
+}
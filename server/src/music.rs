@@ -0,0 +1,79 @@
+use crate::{Context, app_error::ServerResult, auth::User, probe::cached_probe};
+use axum::{Json, extract::State};
+use common::{AUDIO_X, Track};
+use futures_util::{StreamExt, stream};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// how many files are probed at once while building the library
+const PARALLEL_PROBES: usize = 8;
+
+/// every audio file under `base` with its tags, ordered by artist, album and track
+pub async fn music(
+    State(Context {
+        shares,
+        cache_dir,
+        processes,
+        ..
+    }): State<Context>,
+    user: User,
+    Json(base): Json<PathBuf>,
+) -> ServerResult<Json<Vec<Track>>> {
+    let dir = shares.resolve(&base, &user)?;
+    let files = audio_files(&dir).await?;
+    let mut tracks = stream::iter(files)
+        .map(|source| {
+            let (cache_dir, processes, dir, base) = (&cache_dir, &processes, &dir, &base);
+            async move {
+                let info = cached_probe(cache_dir, processes, &source).await.ok();
+                Track {
+                    path: source
+                        .strip_prefix(dir)
+                        .map(|x| base.join(x))
+                        .unwrap_or(source.clone()),
+                    duration: info.as_ref().and_then(|x| x.duration),
+                    tags: info.map(|x| x.tags).unwrap_or_default(),
+                }
+            }
+        })
+        .buffer_unordered(PARALLEL_PROBES)
+        .collect::<Vec<_>>()
+        .await;
+
+    let key = |x: &Track| {
+        (
+            x.artist().to_string(),
+            x.album().to_string(),
+            x.tags.disc,
+            x.tags.track,
+            x.title(),
+        )
+    };
+    tracks.sort_by_cached_key(key);
+    Ok(Json(tracks))
+}
+
+/// audio files under `dir`, hidden files and directories are skipped
+async fn audio_files(dir: &Path) -> ServerResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| AUDIO_X.contains(&x.to_lowercase().as_str()))
+            {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,228 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+    processes::Processes,
+    thumbnail::{cache_name, cached_thumbnail, jpeg_response, partial_path},
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::{fs, process::Command};
+
+const DEFAULT_POSTER_SIZE: u32 = 256;
+const MAX_POSTER_SIZE: u32 = 1024;
+const DEFAULT_PREVIEW_FRAMES: u32 = 10;
+const MAX_PREVIEW_FRAMES: u32 = 50;
+const PREVIEW_FRAME_WIDTH: u32 = 320;
+const PREVIEW_FRAME_HEIGHT: u32 = 180;
+/// album art next to the tracks, checked before the embedded picture
+const COVER_NAMES: [&str; 4] = ["cover", "folder", "front", "album"];
+const COVER_X: [&str; 3] = ["jpg", "jpeg", "png"];
+
+#[derive(Deserialize)]
+pub struct PosterQuery {
+    path: PathBuf,
+    size: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    path: PathBuf,
+    frames: Option<u32>,
+}
+
+pub async fn poster(
+    State(Context {
+        shares,
+        cache_dir,
+        processes,
+        ..
+    }): State<Context>,
+    user: User,
+    Query(PosterQuery { path, size }): Query<PosterQuery>,
+) -> ServerResult<impl IntoResponse> {
+    let size = size
+        .unwrap_or(DEFAULT_POSTER_SIZE)
+        .clamp(1, MAX_POSTER_SIZE);
+    let source = shares.resolve(&path, &user)?;
+    let cached = cached_path(&cache_dir, &source, ("poster", size)).await?;
+
+    if !fs::try_exists(&cached).await? {
+        // a tenth into the video skips most black intros and title cards
+        let at = duration(&processes, &source).await.unwrap_or_default() / 10.;
+        let scale = format!("scale={size}:{size}:force_original_aspect_ratio=decrease");
+        ffmpeg_frame(&processes, &source, &cached, |command| {
+            command
+                .args(["-ss", &at.to_string(), "-i"])
+                .arg(&source)
+                .args(["-frames:v", "1", "-vf", &scale]);
+        })
+        .await?;
+    }
+
+    jpeg_response(cached).await
+}
+
+/// album art of an audio file, from a picture in its directory or the one embedded in its tags
+pub async fn cover(
+    State(Context {
+        shares,
+        cache_dir,
+        processes,
+        ..
+    }): State<Context>,
+    user: User,
+    Query(PosterQuery { path, size }): Query<PosterQuery>,
+) -> ServerResult<Response> {
+    let size = size
+        .unwrap_or(DEFAULT_POSTER_SIZE)
+        .clamp(1, MAX_POSTER_SIZE);
+
+    let source = shares.resolve(&path, &user)?;
+    if let Some(sidecar) = sidecar_cover(&source).await {
+        let cached = cached_thumbnail(&cache_dir, sidecar, size).await?;
+        return Ok(jpeg_response(cached).await?.into_response());
+    }
+
+    let cached = cached_path(&cache_dir, &source, ("cover", size)).await?;
+    if !fs::try_exists(&cached).await? {
+        let scale = format!("scale={size}:{size}:force_original_aspect_ratio=decrease");
+        let embedded = ffmpeg_frame(&processes, &source, &cached, |command| {
+            command
+                .arg("-i")
+                .arg(&source)
+                .args(["-an", "-frames:v", "1", "-vf", &scale]);
+        })
+        .await;
+        if embedded.is_err() {
+            return Ok(StatusCode::NOT_FOUND.into_response());
+        }
+    }
+
+    Ok(jpeg_response(cached).await?.into_response())
+}
+
+/// `Cover.jpg` and friends in the directory of `track`
+async fn sidecar_cover(track: &Path) -> Option<PathBuf> {
+    let mut entries = fs::read_dir(track.parent()?).await.ok()?;
+    let mut best: Option<(usize, PathBuf)> = None;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let lower = |x: Option<&std::ffi::OsStr>| x?.to_str().map(str::to_lowercase);
+        let (Some(stem), Some(extension)) = (lower(path.file_stem()), lower(path.extension()))
+        else {
+            continue;
+        };
+        let Some(rank) = COVER_NAMES.iter().position(|x| *x == stem) else {
+            continue;
+        };
+        if COVER_X.contains(&extension.as_str()) && best.as_ref().is_none_or(|(x, _)| rank < *x) {
+            best = Some((rank, path));
+        }
+    }
+    best.map(|(_, x)| x)
+}
+
+/// a single row sprite sheet of `frames` equally spaced 16:9 frames
+pub async fn preview(
+    State(Context {
+        shares,
+        cache_dir,
+        processes,
+        ..
+    }): State<Context>,
+    user: User,
+    Query(PreviewQuery { path, frames }): Query<PreviewQuery>,
+) -> ServerResult<impl IntoResponse> {
+    let frames = frames
+        .unwrap_or(DEFAULT_PREVIEW_FRAMES)
+        .clamp(1, MAX_PREVIEW_FRAMES);
+    let source = shares.resolve(&path, &user)?;
+    let cached = cached_path(&cache_dir, &source, ("preview", frames)).await?;
+
+    if !fs::try_exists(&cached).await? {
+        let duration = duration(&processes, &source).await?;
+        let (w, h) = (PREVIEW_FRAME_WIDTH, PREVIEW_FRAME_HEIGHT);
+        let filter = format!(
+            "fps={frames}/{duration},scale={w}:{h}:force_original_aspect_ratio=decrease,\
+             pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,tile={frames}x1"
+        );
+        ffmpeg_frame(&processes, &source, &cached, |command| {
+            command
+                .arg("-i")
+                .arg(&source)
+                .args(["-frames:v", "1", "-vf", &filter]);
+        })
+        .await?;
+    }
+
+    jpeg_response(cached).await
+}
+
+async fn cached_path(
+    cache_dir: &Path,
+    source: &Path,
+    variant: (&str, u32),
+) -> ServerResult<PathBuf> {
+    let modified = fs::metadata(source).await?.modified()?;
+    let dir = cache_dir.join("posters");
+    fs::create_dir_all(&dir).await?;
+    Ok(dir.join(cache_name(source, modified, variant, "jpg")))
+}
+
+/// runs ffmpeg with the input arguments set by `input` and writes one jpeg frame to `to`
+async fn ffmpeg_frame<F>(
+    processes: &Processes,
+    source: &Path,
+    to: &Path,
+    input: F,
+) -> ServerResult<()>
+where
+    F: FnOnce(&mut Command),
+{
+    let _permit = processes.permit().await;
+    let partial = partial_path(to);
+    let mut command = Command::new("ffmpeg");
+    command.args(["-v", "error", "-y"]);
+    input(&mut command);
+    let status = command
+        .arg(&partial)
+        .status()
+        .await
+        .map_err(|_| ServerError::FfmpagSpawn(source.to_path_buf()))?;
+    if !status.success() {
+        let _ = fs::remove_file(partial).await;
+        return Err(ServerError::FfmpagWait(source.to_path_buf()));
+    }
+    fs::rename(partial, to).await?;
+    Ok(())
+}
+
+/// length of the media in seconds as reported by ffprobe
+pub(crate) async fn duration(processes: &Processes, source: &Path) -> ServerResult<f64> {
+    let _permit = processes.permit().await;
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(source)
+        .output()
+        .await
+        .map_err(|_| ServerError::FfmpagSpawn(source.to_path_buf()))?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|x| *x > 0.)
+        .ok_or(ServerError::FfmpagWait(source.to_path_buf()))
+}
@@ -0,0 +1,239 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+    processes::Processes,
+    thumbnail::{cache_name, partial_path},
+};
+use axum::{Json, extract::State};
+use common::{AudioTags, MediaInfo, MediaStream, StreamKind};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, process::Command, task::JoinSet};
+
+/// probes every target, files ffprobe can not read come back as `None`
+pub async fn probe(
+    State(Context {
+        shares,
+        cache_dir,
+        processes,
+        ..
+    }): State<Context>,
+    user: User,
+    Json(targets): Json<Vec<PathBuf>>,
+) -> ServerResult<Json<Vec<Option<MediaInfo>>>> {
+    let mut set = JoinSet::new();
+    for (i, target) in targets.into_iter().enumerate() {
+        let source = shares.resolve(&target, &user);
+        let (cache_dir, processes) = (cache_dir.clone(), processes.clone());
+        set.spawn(async move {
+            let info = match source {
+                Ok(source) => cached_probe(&cache_dir, &processes, &source).await.ok(),
+                Err(_) => None,
+            };
+            (i, info)
+        });
+    }
+
+    let mut infos = Vec::new();
+    while let Some(x) = set.join_next().await {
+        infos.push(x?);
+    }
+    infos.sort_by_key(|(i, _)| *i);
+    Ok(Json(infos.into_iter().map(|(_, x)| x).collect()))
+}
+
+pub(crate) async fn cached_probe(
+    cache_dir: &Path,
+    processes: &Processes,
+    source: &Path,
+) -> ServerResult<MediaInfo> {
+    let modified = fs::metadata(source).await?.modified()?;
+    let dir = cache_dir.join("probes");
+    let cached = dir.join(cache_name(source, modified, "probe", "json"));
+    if let Some(info) = fs::read(&cached)
+        .await
+        .ok()
+        .and_then(|x| serde_json::from_slice(&x).ok())
+    {
+        return Ok(info);
+    }
+
+    let info = ffprobe(processes, source).await?;
+    fs::create_dir_all(&dir).await?;
+    let partial = partial_path(&cached);
+    fs::write(&partial, serde_json::to_vec(&info).unwrap_or_default()).await?;
+    fs::rename(partial, cached).await?;
+    Ok(info)
+}
+
+#[derive(Deserialize)]
+struct Probe {
+    format: ProbeFormat,
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    format_name: String,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    channels: Option<u32>,
+    sample_rate: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+impl From<ProbeStream> for MediaStream {
+    fn from(value: ProbeStream) -> Self {
+        let kind = match value.codec_type.as_deref() {
+            Some("video") => StreamKind::Video,
+            Some("audio") => StreamKind::Audio,
+            Some("subtitle") => StreamKind::Subtitle,
+            _ => StreamKind::Other,
+        };
+        let mut tags = value.tags;
+        Self {
+            kind,
+            codec: value.codec_name.unwrap_or_default(),
+            width: value.width,
+            height: value.height,
+            channels: value.channels,
+            sample_rate: value.sample_rate.and_then(|x| x.parse().ok()),
+            language: tags.remove("language"),
+            title: tags.remove("title"),
+        }
+    }
+}
+
+/// ffmpeg names most tags the same whatever the container, only the case differs
+fn audio_tags(tags: HashMap<String, String>) -> AudioTags {
+    let mut tags: HashMap<_, _> = tags
+        .into_iter()
+        .map(|(k, v)| (k.to_lowercase(), v.trim().to_string()))
+        .filter(|(_, v)| !v.is_empty())
+        .collect();
+    // `3/12` track and disc numbers
+    let number = |x: Option<String>| x?.split('/').next()?.trim().parse().ok();
+    AudioTags {
+        title: tags.remove("title"),
+        artist: tags.remove("artist"),
+        album: tags.remove("album"),
+        album_artist: tags.remove("album_artist").or(tags.remove("albumartist")),
+        track: number(tags.remove("track").or(tags.remove("tracknumber"))),
+        disc: number(tags.remove("disc").or(tags.remove("discnumber"))),
+    }
+}
+
+impl From<Probe> for MediaInfo {
+    fn from(Probe { format, streams }: Probe) -> Self {
+        // ogg keeps its vorbis comments on the audio stream instead of the container, elsewhere
+        // stream tags name the stream and not the track
+        let ogg = format.format_name.split(',').any(|x| x == "ogg");
+        let tags = match streams
+            .iter()
+            .find(|x| x.codec_type.as_deref() == Some("audio"))
+        {
+            Some(audio) if ogg && format.tags.is_empty() => audio.tags.clone(),
+            _ => format.tags,
+        };
+        Self {
+            tags: audio_tags(tags),
+            format: format.format_name,
+            duration: format.duration.and_then(|x| x.parse().ok()),
+            bit_rate: format.bit_rate.and_then(|x| x.parse().ok()),
+            // cover art shows up as a single frame video stream
+            streams: streams
+                .into_iter()
+                .map(MediaStream::from)
+                .filter(|x| !(x.kind == StreamKind::Video && x.codec == "mjpeg"))
+                .collect(),
+        }
+    }
+}
+
+async fn ffprobe(processes: &Processes, source: &Path) -> ServerResult<MediaInfo> {
+    let _permit = processes.permit().await;
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+        ])
+        .arg(source)
+        .output()
+        .await
+        .map_err(|_| ServerError::FfmpagSpawn(source.to_path_buf()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(ServerError::Ffmpeg(source.to_path_buf(), stderr));
+    }
+    serde_json::from_slice::<Probe>(&output.stdout)
+        .map(MediaInfo::from)
+        .map_err(|_| ServerError::FfmpagWait(source.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_json_to_media_info() {
+        let json = r#"{
+            "streams": [
+                {"codec_type": "video", "codec_name": "hevc", "width": 1920, "height": 1080},
+                {"codec_type": "audio", "codec_name": "ac3", "channels": 6, "sample_rate": "48000",
+                 "tags": {"language": "eng", "title": "Surround", "TRACKNUMBER": "3/12"}},
+                {"codec_type": "video", "codec_name": "mjpeg", "width": 600, "height": 600}
+            ],
+            "format": {"format_name": "matroska,webm", "duration": "3725.120000", "bit_rate": "5200000"}
+        }"#;
+        let info = MediaInfo::from(serde_json::from_str::<Probe>(json).unwrap());
+
+        assert_eq!(info.duration_text().as_deref(), Some("1:02:05"));
+        assert_eq!(info.bit_rate, Some(5_200_000));
+        assert_eq!(info.streams.len(), 2);
+        assert_eq!(info.video().and_then(|x| x.height), Some(1080));
+        let audio = info.audio().next().unwrap();
+        assert_eq!(
+            (audio.sample_rate, audio.language.as_deref()),
+            (Some(48000), Some("eng"))
+        );
+        assert!(!info.browser_friendly());
+        // the audio stream's title is not the one of the file
+        assert_eq!(info.tags.title, None);
+        assert_eq!(info.tags.track, None);
+    }
+
+    #[test]
+    fn ogg_tags_come_from_the_stream() {
+        let json = r#"{
+            "streams": [
+                {"codec_type": "audio", "codec_name": "opus", "channels": 2, "sample_rate": "48000",
+                 "tags": {"TITLE": "Song", "TRACKNUMBER": "3/12"}}
+            ],
+            "format": {"format_name": "ogg", "duration": "200.000000"}
+        }"#;
+        let info = MediaInfo::from(serde_json::from_str::<Probe>(json).unwrap());
+
+        assert_eq!(info.tags.track, Some(3));
+        assert_eq!(info.tags.title.as_deref(), Some("Song"));
+    }
+}
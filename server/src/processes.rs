@@ -0,0 +1,88 @@
+use axum::{Json, extract::State};
+use common::ProcessStatus;
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::Context;
+
+/// bounds how many external processes (ffmpeg, ffprobe, mount) run at once
+#[derive(Clone)]
+pub(crate) struct Processes {
+    semaphore: Arc<Semaphore>,
+    limit: usize,
+    pending: Arc<AtomicUsize>,
+}
+
+/// counts a waiter as pending until it gets its permit or gives up
+struct Pending(Arc<AtomicUsize>);
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Processes {
+    pub(crate) fn new(limit: usize) -> Self {
+        let limit = limit.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            limit,
+            pending: Default::default(),
+        }
+    }
+
+    /// waits for a free slot, the process may run for as long as the permit is held
+    pub(crate) async fn permit(&self) -> OwnedSemaphorePermit {
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        let _pending = Pending(self.pending.clone());
+        // the semaphore is never closed
+        self.semaphore.clone().acquire_owned().await.unwrap()
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub(crate) fn status(&self) -> ProcessStatus {
+        ProcessStatus {
+            running: self.limit - self.semaphore.available_permits(),
+            pending: self.pending.load(Ordering::Relaxed),
+            limit: self.limit,
+        }
+    }
+}
+
+pub async fn processes(State(Context { processes, .. }): State<Context>) -> Json<ProcessStatus> {
+    Json(processes.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn permits_queue_past_the_limit() {
+        let processes = Processes::new(2);
+        let first = processes.permit().await;
+        let _second = processes.permit().await;
+
+        let waiting = tokio::spawn({
+            let processes = processes.clone();
+            async move {
+                let _third = processes.permit().await;
+            }
+        });
+        tokio::task::yield_now().await;
+        let status = processes.status();
+        assert_eq!((status.running, status.pending), (2, 1));
+
+        drop(first);
+        waiting.await.unwrap();
+        let status = processes.status();
+        assert_eq!((status.running, status.pending), (1, 0));
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    app_error::{ServerError, ServerResult},
+    shares::Shares,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, sync::Mutex};
+
+/// taken while the ledger is read and written back
+static LEDGER: Mutex<()> = Mutex::const_new(());
+
+/// limits on how much uploads may store
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quotas {
+    /// bytes the subtree of each directory, as clients name it, may hold
+    pub dirs: BTreeMap<PathBuf, u64>,
+    /// bytes the uploads of each basic auth user may add up to
+    pub users: HashMap<String, u64>,
+    /// bytes always left free on the filesystem of every share
+    pub min_free: u64,
+}
+
+impl Quotas {
+    pub fn dir(mut self, dir: PathBuf, bytes: u64) -> Self {
+        self.dirs.insert(dir, bytes);
+        self
+    }
+    pub fn user(mut self, user: String, bytes: u64) -> Self {
+        self.users.insert(user, bytes);
+        self
+    }
+    pub fn min_free(self, min_free: u64) -> Self {
+        Self { min_free, ..self }
+    }
+
+    /// how many more bytes `to`, as clients name it, may take
+    pub(crate) async fn remaining(
+        &self,
+        shares: &Shares,
+        state_dir: &Path,
+        to: &Path,
+        user: Option<&str>,
+    ) -> ServerResult<u64> {
+        let mut budget = self.budget(shares, state_dir, user).await;
+        budget.remaining(to).await
+    }
+
+    /// what `user` may store, for requests writing several files
+    pub(crate) async fn budget<'a>(
+        &'a self,
+        shares: &'a Shares,
+        state_dir: &Path,
+        user: Option<&str>,
+    ) -> Budget<'a> {
+        let user = match user.and_then(|x| self.users.get_key_value(x)) {
+            Some((user, limit)) => {
+                let used = Ledger::load(state_dir).await.usage(shares, user).await;
+                Some((*limit, used))
+            }
+            None => None,
+        };
+        Budget {
+            quotas: self,
+            shares,
+            dirs: HashMap::new(),
+            user,
+        }
+    }
+
+    /// remembers that `user` uploaded `to`, only needed when users have quotas
+    pub(crate) async fn record(&self, state_dir: &Path, user: Option<&str>, to: &Path) {
+        let Some(user) = user.filter(|x| self.users.contains_key(*x)) else {
+            return;
+        };
+        let _lock = LEDGER.lock().await;
+        let mut ledger = Ledger::load(state_dir).await;
+        let files = ledger.0.entry(user.to_string()).or_default();
+        if !files.iter().any(|x| x == to) {
+            files.push(to.to_path_buf());
+        }
+        ledger.save(state_dir).await;
+    }
+
+    /// keeps counting the uploads under `from` once they were moved to `to`, both as clients
+    /// name them
+    pub(crate) async fn moved(&self, state_dir: &Path, from: &Path, to: &Path) {
+        if self.users.is_empty() {
+            return;
+        }
+        let _lock = LEDGER.lock().await;
+        let mut ledger = Ledger::load(state_dir).await;
+        let mut changed = false;
+        for file in ledger.0.values_mut().flatten() {
+            if let Ok(rest) = file.strip_prefix(from) {
+                *file = if rest.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(rest)
+                };
+                changed = true;
+            }
+        }
+        if changed {
+            ledger.save(state_dir).await;
+        }
+    }
+}
+
+/// what one request may still write, the directories and uploads it is limited by are only
+/// measured once
+pub(crate) struct Budget<'a> {
+    quotas: &'a Quotas,
+    shares: &'a Shares,
+    /// bytes held under the directory quotas measured so far
+    dirs: HashMap<PathBuf, u64>,
+    /// the quota of the user and how much of it is used
+    user: Option<(u64, u64)>,
+}
+
+impl Budget<'_> {
+    /// how many more bytes `to`, as clients name it, may take
+    pub(crate) async fn remaining(&mut self, to: &Path) -> ServerResult<u64> {
+        let free = free_space(self.shares.share_dir(to)?)?;
+        let mut remaining = free.saturating_sub(self.quotas.min_free);
+        for (dir, limit) in self.quotas.dirs.iter().filter(|(x, _)| to.starts_with(x)) {
+            let used = match self.dirs.get(dir) {
+                Some(x) => *x,
+                None => {
+                    let used = dir_size(&self.shares.locate(dir)?).await;
+                    *self.dirs.entry(dir.clone()).or_insert(used)
+                }
+            };
+            remaining = remaining.min(limit.saturating_sub(used));
+        }
+        if let Some((limit, used)) = self.user {
+            remaining = remaining.min(limit.saturating_sub(used));
+        }
+        Ok(remaining)
+    }
+
+    /// counts `bytes` written to `to` against every quota it falls under
+    pub(crate) fn spend(&mut self, to: &Path, bytes: u64) {
+        for (_, used) in self.dirs.iter_mut().filter(|(x, _)| to.starts_with(x)) {
+            *used += bytes;
+        }
+        if let Some((_, used)) = &mut self.user {
+            *used += bytes;
+        }
+    }
+}
+
+/// bytes an unprivileged process may still write on the filesystem holding `path`
+pub(crate) fn free_space(path: &Path) -> ServerResult<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(std::io::Error::from)?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+pub(crate) async fn dir_size(dir: &Path) -> u64 {
+    let mut size = 0;
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(mut entries) = fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            match entry.metadata().await {
+                Ok(x) if x.is_dir() => dirs.push(entry.path()),
+                Ok(x) => size += x.len(),
+                Err(_) => (),
+            }
+        }
+    }
+    size
+}
+
+/// which files each user uploaded, as clients name them
+#[derive(Default, Serialize, Deserialize)]
+struct Ledger(HashMap<String, Vec<PathBuf>>);
+
+impl Ledger {
+    fn path(state_dir: &Path) -> PathBuf {
+        state_dir.join("quotas").join("users.json")
+    }
+
+    async fn load(state_dir: &Path) -> Self {
+        fs::read(Self::path(state_dir))
+            .await
+            .ok()
+            .and_then(|x| serde_json::from_slice(&x).ok())
+            .unwrap_or_default()
+    }
+
+    async fn save(&self, state_dir: &Path) {
+        let path = Self::path(state_dir);
+        let _ = fs::create_dir_all(state_dir.join("quotas")).await;
+        let _ = fs::write(path, serde_json::to_vec(self).unwrap_or_default()).await;
+    }
+
+    /// deleted uploads stop counting
+    async fn usage(&self, shares: &Shares, user: &str) -> u64 {
+        let mut size = 0;
+        for file in self.0.get(user).into_iter().flatten() {
+            let Ok(file) = shares.locate(file) else {
+                continue;
+            };
+            if let Ok(x) = fs::metadata(file).await {
+                size += x.len();
+            }
+        }
+        size
+    }
+}
+
+/// fails once more than `remaining` bytes were written
+pub(crate) fn check(path: &Path, written: u64, remaining: u64) -> ServerResult<()> {
+    if written > remaining {
+        return Err(ServerError::InsufficientStorage(
+            path.to_path_buf(),
+            remaining,
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+};
+use axum::{Json, extract::State};
+use common::ShareInfo;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+/// a directory served under its own name at the top of the tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Share {
+    /// the root share, the one [`crate::Server::new`] makes, has no name
+    pub name: String,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub read_only: bool,
+    /// basic auth users that may see the share, everyone when empty
+    #[serde(default)]
+    pub users: Vec<String>,
+}
+
+impl Share {
+    pub fn new(name: String, path: PathBuf) -> Self {
+        Self {
+            name,
+            path,
+            read_only: false,
+            users: Vec::new(),
+        }
+    }
+    pub fn read_only(self, read_only: bool) -> Self {
+        Self { read_only, ..self }
+    }
+    pub fn users(self, users: Vec<String>) -> Self {
+        Self { users, ..self }
+    }
+
+    fn allows(&self, user: &User) -> bool {
+        self.users.is_empty() || user.0.as_ref().is_some_and(|x| self.users.contains(x))
+    }
+}
+
+/// every share, paths from clients start with a share name unless they are in the root share
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Shares(Vec<Share>);
+
+impl Shares {
+    pub(crate) fn new(shares: Vec<Share>) -> Self {
+        Self(shares)
+    }
+
+    fn root(&self) -> Option<&Share> {
+        self.0.iter().find(|x| x.name.is_empty())
+    }
+
+    /// the share holding `path` and the rest of the path inside it
+    fn find<'a>(&self, path: &'a Path) -> ServerResult<(&Share, &'a Path)> {
+        // nothing may climb out of its share
+        if !path.components().all(|x| matches!(x, Component::Normal(_))) {
+            return Err(ServerError::Forbidden(path.to_path_buf()));
+        }
+        let mut components = path.components();
+        let named = components.next().and_then(|first| {
+            self.0
+                .iter()
+                .find(|x| !x.name.is_empty() && first.as_os_str() == x.name.as_str())
+        });
+        match named {
+            Some(share) => Ok((share, components.as_path())),
+            None => self
+                .root()
+                .map(|x| (x, path))
+                .ok_or_else(|| ServerError::ShareNotFound(path.to_path_buf())),
+        }
+    }
+
+    /// where `path` is on disk, whoever asks
+    pub(crate) fn locate(&self, path: &Path) -> ServerResult<PathBuf> {
+        let (share, rest) = self.find(path)?;
+        Ok(share.path.join(rest))
+    }
+
+    /// where `path` is on disk, if `user` may read it
+    pub(crate) fn resolve(&self, path: &Path, user: &User) -> ServerResult<PathBuf> {
+        let (share, rest) = self.find(path)?;
+        if !share.allows(user) {
+            return Err(ServerError::Forbidden(path.to_path_buf()));
+        }
+        Ok(share.path.join(rest))
+    }
+
+    /// where `path` is on disk, if `user` may change it
+    pub(crate) fn writable(&self, path: &Path, user: &User) -> ServerResult<PathBuf> {
+        let (share, _) = self.find(path)?;
+        if share.read_only {
+            return Err(ServerError::ReadOnly(path.to_path_buf()));
+        }
+        self.resolve(path, user)
+    }
+
+    /// like [`Self::writable`], but the top of a share can not be moved or deleted
+    pub(crate) fn removable(&self, path: &Path, user: &User) -> ServerResult<PathBuf> {
+        let (_, rest) = self.find(path)?;
+        if rest.as_os_str().is_empty() {
+            return Err(ServerError::Forbidden(path.to_path_buf()));
+        }
+        self.writable(path, user)
+    }
+
+    /// the directory on disk of the share holding `path`
+    pub(crate) fn share_dir(&self, path: &Path) -> ServerResult<&Path> {
+        Ok(&self.find(path)?.0.path)
+    }
+
+    /// the named shares `user` may see
+    pub(crate) fn listed(&self, user: &User) -> impl Iterator<Item = &Share> {
+        self.0
+            .iter()
+            .filter(|x| !x.name.is_empty() && x.allows(user))
+    }
+
+    /// what clients call the file at `real`, if any share holds it
+    pub(crate) fn virtual_path(&self, real: &Path) -> Option<PathBuf> {
+        self.0
+            .iter()
+            .filter_map(|x| Some((x, real.strip_prefix(&x.path).ok()?)))
+            // a share inside another one is the better match
+            .max_by_key(|(x, _)| x.path.components().count())
+            .map(|(x, rest)| Path::new(&x.name).join(rest))
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Share> {
+        self.0.iter()
+    }
+}
+
+/// the named shares the caller may see
+pub async fn shares(
+    State(Context { shares, .. }): State<Context>,
+    user: User,
+) -> Json<Vec<ShareInfo>> {
+    Json(
+        shares
+            .listed(&user)
+            .map(|x| ShareInfo {
+                name: x.name.clone(),
+                read_only: x.read_only,
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn share_resolution() {
+        let shares = Shares::new(vec![
+            Share::new(String::new(), "/srv/root".into()),
+            Share::new("media".into(), "/mnt/media".into()).read_only(true),
+            Share::new("docs".into(), "/home/docs".into()).users(vec!["alice".into()]),
+        ]);
+        let (anyone, alice) = (User(None), User(Some("alice".into())));
+
+        assert_eq!(
+            shares.resolve(Path::new("media/a.mkv"), &anyone).unwrap(),
+            Path::new("/mnt/media/a.mkv")
+        );
+        assert_eq!(
+            shares.resolve(Path::new("other/a.txt"), &anyone).unwrap(),
+            Path::new("/srv/root/other/a.txt")
+        );
+        assert!(matches!(
+            shares.writable(Path::new("media/a.mkv"), &anyone),
+            Err(ServerError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            shares.resolve(Path::new("docs/a.txt"), &anyone),
+            Err(ServerError::Forbidden(_))
+        ));
+        assert!(shares.writable(Path::new("docs/a.txt"), &alice).is_ok());
+        assert!(matches!(
+            shares.removable(Path::new("docs"), &alice),
+            Err(ServerError::Forbidden(_))
+        ));
+        assert!(matches!(
+            shares.resolve(Path::new("media/../../etc/passwd"), &anyone),
+            Err(ServerError::Forbidden(_))
+        ));
+        assert_eq!(
+            shares.virtual_path(Path::new("/mnt/media/show/e1.mkv")),
+            Some("media/show/e1.mkv".into())
+        );
+        assert_eq!(shares.listed(&anyone).count(), 1);
+    }
+}
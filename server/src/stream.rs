@@ -0,0 +1,329 @@
+use crate::{Context, app_error::ServerResult, auth::User};
+use axum::{
+    body::{Body, Bytes},
+    extract::{self, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use futures_util::{StreamExt, stream};
+use std::{
+    fs::Metadata,
+    io::{ErrorKind, SeekFrom},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt, Take},
+};
+use tokio_util::io::ReaderStream;
+
+/// more ranges than this in one request are answered with the whole file
+const MAX_RANGES: usize = 16;
+const BOUNDARY: &str = "webls_byteranges";
+
+pub async fn download(
+    State(Context { shares, .. }): State<Context>,
+    user: User,
+    extract::Path(path): extract::Path<PathBuf>,
+    headers: HeaderMap,
+) -> ServerResult<Response> {
+    let path = shares.resolve(&path, &user)?;
+    file(&path, &headers).await
+}
+
+/// the file at `path` on disk, or the ranges of it `headers` ask for
+pub(crate) async fn file(path: &Path, headers: &HeaderMap) -> ServerResult<Response> {
+    let metadata = match fs::metadata(path).await {
+        Ok(x) if x.is_file() => x,
+        Ok(_) => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            return Ok(StatusCode::NOT_FOUND.into_response());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let len = metadata.len();
+    let validators = Validators::new(&metadata);
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+
+    let mut response = if validators.not_modified(headers) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        match requested(&validators, headers, len) {
+            None | Some(Ranges::Ignored) => full(path, len, mime.as_ref()).await?,
+            Some(Ranges::Unsatisfiable) => unsatisfiable(len),
+            Some(Ranges::Satisfiable(ranges)) => match ranges.as_slice() {
+                [range] => single(path, len, mime.as_ref(), range.clone()).await?,
+                _ => multiple(path, len, mime.as_ref(), ranges).await?,
+            },
+        }
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&validators.etag).unwrap(),
+    );
+    headers.insert(
+        header::LAST_MODIFIED,
+        HeaderValue::from_str(&httpdate::fmt_http_date(validators.modified)).unwrap(),
+    );
+    Ok(response)
+}
+
+/// the ranges of a `len` bytes file `headers` ask for, none when they want all of it
+fn requested(validators: &Validators, headers: &HeaderMap, len: u64) -> Option<Ranges> {
+    let range = headers
+        .get(header::RANGE)
+        .filter(|_| validators.if_range(headers))
+        .and_then(|x| x.to_str().ok());
+    range.map(|x| parse_ranges(x, len))
+}
+
+/// whether [`file`] answers `headers` with the first byte of the file, which tells a download
+/// apart from the rest of one that is resumed or seeked into
+pub(crate) fn sends_start(metadata: &Metadata, headers: &HeaderMap) -> bool {
+    let validators = Validators::new(metadata);
+    if validators.not_modified(headers) {
+        return false;
+    }
+    match requested(&validators, headers, metadata.len()) {
+        None | Some(Ranges::Ignored) => true,
+        Some(Ranges::Unsatisfiable) => false,
+        Some(Ranges::Satisfiable(ranges)) => ranges.iter().any(|x| *x.start() == 0),
+    }
+}
+
+struct Validators {
+    etag: String,
+    modified: SystemTime,
+}
+
+impl Validators {
+    fn new(metadata: &Metadata) -> Self {
+        let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+        let nanos = modified
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_nanos())
+            .unwrap_or_default();
+        Self {
+            etag: format!("\"{:x}-{nanos:x}\"", metadata.len()),
+            // http dates have second precision
+            modified: UNIX_EPOCH
+                + std::time::Duration::from_secs(
+                    modified
+                        .duration_since(UNIX_EPOCH)
+                        .map(|x| x.as_secs())
+                        .unwrap_or_default(),
+                ),
+        }
+    }
+
+    fn etag_matches(&self, value: &str) -> bool {
+        value
+            .split(',')
+            .map(|x| x.trim().trim_start_matches("W/"))
+            .any(|x| x == "*" || x == self.etag)
+    }
+
+    fn date_header(headers: &HeaderMap, name: header::HeaderName) -> Option<SystemTime> {
+        headers
+            .get(name)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| httpdate::parse_http_date(x).ok())
+    }
+
+    fn not_modified(&self, headers: &HeaderMap) -> bool {
+        match headers.get(header::IF_NONE_MATCH) {
+            Some(value) => value.to_str().is_ok_and(|x| self.etag_matches(x)),
+            None => Self::date_header(headers, header::IF_MODIFIED_SINCE)
+                .is_some_and(|since| self.modified <= since),
+        }
+    }
+
+    /// whether a `Range` header still applies to the representation the client holds
+    fn if_range(&self, headers: &HeaderMap) -> bool {
+        let Some(value) = headers.get(header::IF_RANGE).and_then(|x| x.to_str().ok()) else {
+            return headers.get(header::IF_RANGE).is_none();
+        };
+        if value.starts_with('"') {
+            return value == self.etag;
+        }
+        httpdate::parse_http_date(value).is_ok_and(|x| x == self.modified)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Ranges {
+    /// malformed or unsupported header, serve the whole file
+    Ignored,
+    Unsatisfiable,
+    Satisfiable(Vec<RangeInclusive<u64>>),
+}
+
+fn parse_ranges(value: &str, len: u64) -> Ranges {
+    let Some(specs) = value.trim().strip_prefix("bytes=") else {
+        return Ranges::Ignored;
+    };
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim) {
+        let Some((start, end)) = spec.split_once('-') else {
+            return Ranges::Ignored;
+        };
+        let range = match (start.trim(), end.trim()) {
+            ("", "") => return Ranges::Ignored,
+            ("", suffix) => match suffix.parse::<u64>() {
+                Ok(0) => continue,
+                Ok(suffix) => len.saturating_sub(suffix)..=len.saturating_sub(1),
+                Err(_) => return Ranges::Ignored,
+            },
+            (start, end) => {
+                let Ok(start) = start.parse::<u64>() else {
+                    return Ranges::Ignored;
+                };
+                let end = match end {
+                    "" => len.saturating_sub(1),
+                    end => match end.parse::<u64>() {
+                        Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+                        _ => return Ranges::Ignored,
+                    },
+                };
+                if start >= len {
+                    continue;
+                }
+                start..=end
+            }
+        };
+        if len > 0 {
+            ranges.push(range);
+        }
+    }
+    if ranges.is_empty() {
+        Ranges::Unsatisfiable
+    } else if ranges.len() > MAX_RANGES {
+        Ranges::Ignored
+    } else {
+        Ranges::Satisfiable(ranges)
+    }
+}
+
+async fn read_range(
+    path: &Path,
+    range: &RangeInclusive<u64>,
+) -> ServerResult<ReaderStream<Take<File>>> {
+    let mut file = File::open(path).await?;
+    file.seek(SeekFrom::Start(*range.start())).await?;
+    Ok(ReaderStream::new(
+        file.take(range.end() - range.start() + 1),
+    ))
+}
+
+async fn full(path: &Path, len: u64, mime: &str) -> ServerResult<Response> {
+    let body = Body::from_stream(ReaderStream::new(File::open(path).await?));
+    Ok((
+        [
+            (header::CONTENT_TYPE, mime.to_string()),
+            (header::CONTENT_LENGTH, len.to_string()),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn unsatisfiable(len: u64) -> Response {
+    (
+        StatusCode::RANGE_NOT_SATISFIABLE,
+        [(header::CONTENT_RANGE, format!("bytes */{len}"))],
+    )
+        .into_response()
+}
+
+async fn single(
+    path: &Path,
+    len: u64,
+    mime: &str,
+    range: RangeInclusive<u64>,
+) -> ServerResult<Response> {
+    let body = Body::from_stream(read_range(path, &range).await?);
+    Ok((
+        StatusCode::PARTIAL_CONTENT,
+        [
+            (header::CONTENT_TYPE, mime.to_string()),
+            (
+                header::CONTENT_LENGTH,
+                (range.end() - range.start() + 1).to_string(),
+            ),
+            (
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{len}", range.start(), range.end()),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+async fn multiple(
+    path: &Path,
+    len: u64,
+    mime: &str,
+    ranges: Vec<RangeInclusive<u64>>,
+) -> ServerResult<Response> {
+    let mut parts = Vec::with_capacity(ranges.len());
+    let mut content_length = 0;
+    for range in ranges {
+        let head = format!(
+            "\r\n--{BOUNDARY}\r\nContent-Type: {mime}\r\nContent-Range: bytes {}-{}/{len}\r\n\r\n",
+            range.start(),
+            range.end()
+        );
+        content_length += head.len() as u64 + range.end() - range.start() + 1;
+        parts.push((Bytes::from(head), read_range(path, &range).await?));
+    }
+    let tail = Bytes::from(format!("\r\n--{BOUNDARY}--\r\n"));
+    content_length += tail.len() as u64;
+
+    let stream = stream::iter(parts)
+        .flat_map(|(head, body)| stream::once(async { Ok(head) }).chain(body))
+        .chain(stream::once(async { Ok(tail) }));
+
+    Ok((
+        StatusCode::PARTIAL_CONTENT,
+        [
+            (
+                header::CONTENT_TYPE,
+                format!("multipart/byteranges; boundary={BOUNDARY}"),
+            ),
+            (header::CONTENT_LENGTH, content_length.to_string()),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ranges_test() {
+        use Ranges::*;
+        let one = |range: RangeInclusive<u64>| Satisfiable(vec![range]);
+
+        assert_eq!(parse_ranges("bytes=0-99", 1000), one(0..=99));
+        assert_eq!(parse_ranges("bytes=900-", 1000), one(900..=999));
+        assert_eq!(parse_ranges("bytes=-100", 1000), one(900..=999));
+        assert_eq!(parse_ranges("bytes=950-2000", 1000), one(950..=999));
+        assert_eq!(
+            parse_ranges("bytes=0-0, 10-19", 1000),
+            Satisfiable(vec![0..=0, 10..=19])
+        );
+        assert_eq!(parse_ranges("bytes=1000-", 1000), Unsatisfiable);
+        assert_eq!(parse_ranges("bytes=-0", 1000), Unsatisfiable);
+        assert_eq!(parse_ranges("bytes=20-10", 1000), Ignored);
+        assert_eq!(parse_ranges("items=0-10", 1000), Ignored);
+        assert_eq!(parse_ranges("bytes=a-b", 1000), Ignored);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+    probe::cached_probe,
+    processes::Processes,
+    thumbnail::{cache_name, partial_path},
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use common::{StreamKind, Subtitle};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tokio::{fs, process::Command};
+
+const SIDECAR_X: [&str; 4] = ["srt", "ass", "ssa", "vtt"];
+/// subtitle codecs ffmpeg can turn into WebVTT, bitmap ones like pgs can not
+const TEXT_CODECS: [&str; 6] = ["subrip", "ass", "ssa", "mov_text", "webvtt", "text"];
+
+#[derive(Deserialize)]
+pub struct SubtitleQuery {
+    path: PathBuf,
+    stream: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct SubtitlesQuery {
+    path: PathBuf,
+}
+
+/// sidecar files named after the video followed by its embedded text tracks
+pub async fn subtitles(
+    State(Context {
+        shares,
+        cache_dir,
+        processes,
+        ..
+    }): State<Context>,
+    user: User,
+    Query(SubtitlesQuery { path }): Query<SubtitlesQuery>,
+) -> ServerResult<Json<Vec<Subtitle>>> {
+    let source = shares.resolve(&path, &user)?;
+    let mut subtitles = sidecars(&source, &path).await?;
+
+    if let Ok(info) = cached_probe(&cache_dir, &processes, &source).await {
+        let embedded = info
+            .streams
+            .into_iter()
+            .filter(|x| x.kind == StreamKind::Subtitle)
+            .enumerate()
+            .filter(|(_, x)| TEXT_CODECS.contains(&x.codec.as_str()))
+            .map(|(i, x)| Subtitle {
+                label: x
+                    .title
+                    .or(x.language.clone())
+                    .unwrap_or_else(|| format!("track {}", i + 1)),
+                language: x.language,
+                path: path.clone(),
+                stream: Some(i),
+            });
+        subtitles.extend(embedded);
+    }
+
+    Ok(Json(subtitles))
+}
+
+/// `source` is where `video` is on disk
+async fn sidecars(source: &Path, video: &Path) -> ServerResult<Vec<Subtitle>> {
+    let (Some(dir), Some(source_dir), Some(stem)) = (
+        video.parent(),
+        source.parent(),
+        video.file_stem().and_then(|x| x.to_str()),
+    ) else {
+        return Ok(Vec::new());
+    };
+    let mut entries = fs::read_dir(source_dir).await?;
+    let mut subtitles = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        if let Some(subtitle) = name.to_str().and_then(|x| sidecar(stem, x)) {
+            subtitles.push(Subtitle {
+                path: dir.join(&name),
+                ..subtitle
+            });
+        }
+    }
+    subtitles.sort_by(|a, b| a.label.cmp(&b.label));
+    Ok(subtitles)
+}
+
+/// `movie.srt` or `movie.en.forced.srt` belong to `movie.mkv`, the middle part labels them
+fn sidecar(stem: &str, name: &str) -> Option<Subtitle> {
+    let (rest, extension) = name.strip_prefix(stem)?.rsplit_once('.')?;
+    if !SIDECAR_X.contains(&extension.to_lowercase().as_str()) {
+        return None;
+    }
+    let middle = match rest {
+        "" => None,
+        x => Some(x.strip_prefix('.')?),
+    };
+    let language = middle
+        .and_then(|x| x.split('.').next())
+        .filter(|x| (2..=3).contains(&x.len()) && x.chars().all(|c| c.is_ascii_alphabetic()))
+        .map(str::to_lowercase);
+    Some(Subtitle {
+        label: middle.unwrap_or(extension).to_string(),
+        language,
+        path: PathBuf::from(name),
+        stream: None,
+    })
+}
+
+/// a subtitle converted to WebVTT
+pub async fn subtitle(
+    State(Context {
+        shares,
+        cache_dir,
+        processes,
+        ..
+    }): State<Context>,
+    user: User,
+    Query(SubtitleQuery { path, stream }): Query<SubtitleQuery>,
+) -> ServerResult<impl IntoResponse> {
+    let source = shares.resolve(&path, &user)?;
+    let is_vtt = source
+        .extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("vtt"));
+    let vtt = if is_vtt && stream.is_none() {
+        source
+    } else {
+        let modified = fs::metadata(&source).await?.modified()?;
+        let dir = cache_dir.join("subtitles");
+        let cached = dir.join(cache_name(&source, modified, stream, "vtt"));
+        if !fs::try_exists(&cached).await? {
+            fs::create_dir_all(&dir).await?;
+            to_webvtt(&processes, &source, stream, &cached).await?;
+        }
+        cached
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/vtt; charset=utf-8"),
+            (header::CACHE_CONTROL, "private, max-age=86400"),
+        ],
+        fs::read(vtt).await?,
+    ))
+}
+
+async fn to_webvtt(
+    processes: &Processes,
+    source: &Path,
+    stream: Option<usize>,
+    to: &Path,
+) -> ServerResult<()> {
+    let _permit = processes.permit().await;
+    let partial = partial_path(to);
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-v", "error", "-nostdin", "-y", "-i"])
+        .arg(source);
+    if let Some(stream) = stream {
+        command.args(["-map", &format!("0:s:{stream}")]);
+    }
+    let status = command
+        .args(["-f", "webvtt"])
+        .arg(&partial)
+        .status()
+        .await
+        .map_err(|_| ServerError::FfmpagSpawn(source.to_path_buf()))?;
+    if !status.success() {
+        let _ = fs::remove_file(partial).await;
+        return Err(ServerError::FfmpagWait(source.to_path_buf()));
+    }
+    fs::rename(partial, to).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_names() {
+        let label = |name| sidecar("movie", name).map(|x| (x.label, x.language));
+
+        assert_eq!(label("movie.srt"), Some(("srt".into(), None)));
+        assert_eq!(
+            label("movie.en.srt"),
+            Some(("en".into(), Some("en".into())))
+        );
+        assert_eq!(
+            label("movie.ENG.forced.ass"),
+            Some(("ENG.forced".into(), Some("eng".into())))
+        );
+        assert_eq!(
+            label("movie.director commentary.vtt"),
+            Some(("director commentary".into(), None))
+        );
+        assert_eq!(label("movie.mkv"), None);
+        assert_eq!(label("movie2.srt"), None);
+        assert_eq!(label("other.srt"), None);
+    }
+}
@@ -0,0 +1,37 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+/// an empty directory of its own for a test, gone again once dropped
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` keeps the tests of one run apart, the process id the runs
+    pub(crate) fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("webls-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
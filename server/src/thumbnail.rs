@@ -0,0 +1,274 @@
+use crate::{
+    Context,
+    app_error::{ServerError, ServerResult},
+    auth::User,
+};
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use std::{
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+use tokio::{fs, task::spawn_blocking};
+
+const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+// large enough for the image viewer to use the same cached renditions
+const MAX_THUMBNAIL_SIZE: u32 = 2048;
+const THUMBNAIL_QUALITY: u8 = 80;
+/// renditions older than this are rendered again when asked for
+const MAX_CACHE_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// past this size the oldest renditions go first
+const MAX_CACHE_BYTES: u64 = 1024 * 1024 * 1024;
+const EVICT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Deserialize)]
+pub struct ThumbnailQuery {
+    path: PathBuf,
+    size: Option<u32>,
+}
+
+pub async fn thumbnail(
+    State(Context {
+        shares, cache_dir, ..
+    }): State<Context>,
+    user: User,
+    Query(ThumbnailQuery { path, size }): Query<ThumbnailQuery>,
+) -> ServerResult<impl IntoResponse> {
+    let size = size
+        .unwrap_or(DEFAULT_THUMBNAIL_SIZE)
+        .clamp(1, MAX_THUMBNAIL_SIZE);
+    if !common::thumbnailable(&path) {
+        return Err(ServerError::Image(format!(
+            "can not decode {}",
+            path.display()
+        )));
+    }
+    let cached = cached_thumbnail(&cache_dir, shares.resolve(&path, &user)?, size).await?;
+    jpeg_response(cached).await
+}
+
+/// renders the thumbnail of `source` unless it is cached already
+pub(crate) async fn cached_thumbnail(
+    cache_dir: &Path,
+    source: PathBuf,
+    size: u32,
+) -> ServerResult<PathBuf> {
+    let modified = fs::metadata(&source).await?.modified()?;
+    let cached = cache_dir
+        .join("thumbnails")
+        .join(cache_name(&source, modified, size, "jpg"));
+
+    if !fs::try_exists(&cached).await? {
+        fs::create_dir_all(cache_dir.join("thumbnails")).await?;
+        let to = cached.clone();
+        spawn_blocking(move || render(&source, &to, size)).await??;
+    }
+    Ok(cached)
+}
+
+pub(crate) async fn jpeg_response(cached: PathBuf) -> ServerResult<impl IntoResponse> {
+    let bytes = fs::read(cached).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/jpeg"),
+            (header::CACHE_CONTROL, "private, max-age=86400"),
+        ],
+        bytes,
+    ))
+}
+
+/// cache file name for a rendition of `source`, changes whenever the source is modified
+pub(crate) fn cache_name(
+    source: &Path,
+    modified: SystemTime,
+    variant: impl Hash,
+    extension: &str,
+) -> String {
+    let mut hasher = Blake3::default();
+    source.hash(&mut hasher);
+    modified.hash(&mut hasher);
+    variant.hash(&mut hasher);
+    let hex = hasher.0.finalize().to_hex();
+    format!("{}.{extension}", &hex[..32])
+}
+
+/// feeds what is hashed to blake3, whose output stays the same across rust releases unlike
+/// [`std::hash::DefaultHasher`]
+#[derive(Default)]
+struct Blake3(blake3::Hasher);
+
+impl Hasher for Blake3 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let hash = self.0.finalize();
+        u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap_or_default())
+    }
+}
+
+/// unique sibling of `to` to render into before renaming it in place
+pub(crate) fn partial_path(to: &Path) -> PathBuf {
+    static PARTIALS: AtomicU64 = AtomicU64::new(0);
+    let n = PARTIALS.fetch_add(1, Ordering::Relaxed);
+    let extension = to.extension().and_then(|x| x.to_str()).unwrap_or_default();
+    to.with_extension(format!("{n}.part.{extension}"))
+}
+
+/// drops renditions that are too old, then the oldest while the cache is too big, forever
+pub(crate) async fn evict(cache_dir: PathBuf) {
+    let mut interval = tokio::time::interval(EVICT_INTERVAL);
+    loop {
+        interval.tick().await;
+        let dirs = ["thumbnails", "posters", "probes", "subtitles"].map(|x| cache_dir.join(x));
+        let hls = cache_dir.join("hls");
+        let evicted = spawn_blocking(move || {
+            evict_now(&dirs, MAX_CACHE_AGE, MAX_CACHE_BYTES);
+            evict_transcodes(&hls, MAX_CACHE_AGE);
+        });
+        if let Err(err) = evicted.await {
+            tracing::warn!("cache eviction stopped : {err}");
+        }
+    }
+}
+
+fn evict_now(dirs: &[PathBuf], max_age: Duration, max_bytes: u64) {
+    let now = SystemTime::now();
+    let mut files = dirs
+        .iter()
+        .filter_map(|x| std::fs::read_dir(x).ok())
+        .flatten()
+        .filter_map(|x| {
+            let entry = x.ok()?;
+            let metadata = entry.metadata().ok().filter(|x| x.is_file())?;
+            Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+        })
+        .collect::<Vec<_>>();
+    // newest first, what is left past the budget is the oldest
+    files.sort_by_key(|x| std::cmp::Reverse(x.0));
+    let mut kept = 0;
+    for (modified, len, path) in files {
+        let old = now.duration_since(modified).unwrap_or_default() > max_age;
+        if old || kept + len > max_bytes {
+            let _ = std::fs::remove_file(path);
+        } else {
+            kept += len;
+        }
+    }
+}
+
+/// drops the transcodes in `dir` whose playlist was last written too long ago
+///
+/// they are far bigger than the renditions, so only their age counts. a running one writes its
+/// playlist every segment and those killed while idle are gone already, this is for the rest
+fn evict_transcodes(dir: &Path, max_age: Duration) {
+    let now = SystemTime::now();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let modified = std::fs::metadata(path.join(crate::hls::PLAYLIST))
+            .or_else(|_| entry.metadata())
+            .and_then(|x| x.modified());
+        let Ok(modified) = modified else {
+            continue;
+        };
+        if now.duration_since(modified).unwrap_or_default() > max_age {
+            let _ = std::fs::remove_dir_all(path);
+        }
+    }
+}
+
+fn render(from: &Path, to: &Path, size: u32) -> ServerResult<()> {
+    use image::{DynamicImage, ImageDecoder, ImageReader, codecs::jpeg::JpegEncoder};
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    let mut decoder = ImageReader::open(from)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    // browsers would rotate the original by its exif tag, the re-encoded jpeg has none
+    image.apply_orientation(orientation);
+    let image = image.thumbnail(size, size).to_rgb8();
+
+    // render next to the final name so concurrent requests never read a half written file
+    let partial = partial_path(to);
+    let mut file = BufWriter::new(File::create(&partial)?);
+    JpegEncoder::new_with_quality(&mut file, THUMBNAIL_QUALITY).encode_image(&image)?;
+    file.flush()?;
+    drop(file);
+    std::fs::rename(partial, to)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn render_keeps_aspect_ratio() {
+        use image::{ImageReader, RgbImage};
+
+        let dir = TempDir::new("thumbnail");
+        let from = dir.join("wide.png");
+        let to = dir.join("wide.jpg");
+        RgbImage::new(400, 100).save(&from).unwrap();
+
+        render(&from, &to, 200).unwrap();
+
+        let thumb = ImageReader::open(&to).unwrap().decode().unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (200, 50));
+    }
+
+    #[test]
+    fn eviction_keeps_the_newest_within_budget() {
+        let dir = TempDir::new("evict");
+        let now = SystemTime::now();
+        for (name, age) in [("new", 0), ("older", 10), ("oldest", 20), ("stale", 100)] {
+            let file = std::fs::File::create(dir.join(name)).unwrap();
+            file.set_len(10).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+
+        evict_now(&[dir.to_path_buf()], Duration::from_secs(50), 25);
+
+        let mut left = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|x| x.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        left.sort();
+        assert_eq!(left, ["new", "older"]);
+    }
+
+    #[test]
+    fn old_transcodes_go_whole() {
+        let dir = TempDir::new("evict-hls");
+        let now = SystemTime::now();
+        for (key, age) in [("fresh", 0), ("stale", 100)] {
+            std::fs::create_dir_all(dir.join(key)).unwrap();
+            std::fs::write(dir.join(key).join("segment00000.ts"), "").unwrap();
+            let playlist = std::fs::File::create(dir.join(key).join(crate::hls::PLAYLIST)).unwrap();
+            playlist
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+
+        evict_transcodes(&dir, Duration::from_secs(50));
+
+        let left = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|x| x.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(left, ["fresh"]);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::app_error::{ServerError, ServerResult};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+/// where the certificate https is served with comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tls {
+    /// pem files made elsewhere, by an acme client for example
+    Files { cert: PathBuf, key: PathBuf },
+    /// a certificate made for `names` on first use and kept in `dir`, so its fingerprint stays
+    /// until other names are asked for
+    SelfSigned { dir: PathBuf, names: Vec<String> },
+}
+
+/// a pem encoded certificate chain and its private key
+pub struct Certificate {
+    pub cert: Vec<u8>,
+    pub key: Vec<u8>,
+}
+
+impl Tls {
+    pub fn load(&self) -> ServerResult<Certificate> {
+        match self {
+            Self::Files { cert, key } => Ok(Certificate {
+                cert: fs::read(cert)?,
+                key: fs::read(key)?,
+            }),
+            Self::SelfSigned { dir, names } => self_signed(dir, names),
+        }
+    }
+}
+
+fn self_signed(dir: &Path, names: &[String]) -> ServerResult<Certificate> {
+    let mut names = names.to_vec();
+    if names.is_empty() {
+        names.push(String::from("localhost"));
+    }
+    let (cert, key) = (dir.join(CERT_FILE), dir.join(KEY_FILE));
+    if let (Ok(cert), Ok(key)) = (fs::read(&cert), fs::read(&key))
+        && made_for(&cert, &names)
+    {
+        return Ok(Certificate { cert, key });
+    }
+
+    let generated =
+        rcgen::generate_simple_self_signed(names).map_err(|x| ServerError::Tls(x.to_string()))?;
+    let certificate = Certificate {
+        cert: generated.cert.pem().into_bytes(),
+        key: generated.key_pair.serialize_pem().into_bytes(),
+    };
+    fs::create_dir_all(dir)?;
+    fs::write(&cert, &certificate.cert)?;
+    write_private(&key, &certificate.key)?;
+    Ok(certificate)
+}
+
+/// whether the pem certificate `cert` names just `names`, like the host's address it was made for
+fn made_for(cert: &[u8], names: &[String]) -> bool {
+    let Ok(wanted) = rcgen::CertificateParams::new(names) else {
+        return false;
+    };
+    let stored = std::str::from_utf8(cert)
+        .ok()
+        .and_then(|x| rcgen::CertificateParams::from_ca_cert_pem(x).ok());
+    stored.is_some_and(|x| {
+        let stored = x.subject_alt_names.iter().collect::<HashSet<_>>();
+        stored == wanted.subject_alt_names.iter().collect()
+    })
+}
+
+/// only the owner may read the private key
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(bytes)
+}
+
+impl Certificate {
+    /// sha-256 of the leaf certificate, as browsers show it
+    pub fn fingerprint(&self) -> ServerResult<String> {
+        let der = rustls_pemfile::certs(&mut self.cert.as_slice())
+            .next()
+            .ok_or_else(|| ServerError::Tls(String::from("no certificate in the pem")))??;
+        Ok(Sha256::digest(der)
+            .iter()
+            .map(|x| format!("{x:02X}"))
+            .collect::<Vec<_>>()
+            .join(":"))
+    }
+
+    pub(crate) async fn config(self) -> ServerResult<axum_server::tls_rustls::RustlsConfig> {
+        // whoever installed one first wins, they are all the same provider
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        axum_server::tls_rustls::RustlsConfig::from_pem(self.cert, self.key)
+            .await
+            .map_err(|x| ServerError::Tls(x.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn self_signed_is_kept() {
+        let dir = TempDir::new("tls");
+        let tls = Tls::SelfSigned {
+            dir: dir.to_path_buf(),
+            names: vec![String::from("192.168.1.2")],
+        };
+        let first = tls.load().unwrap().fingerprint().unwrap();
+        let second = tls.load().unwrap().fingerprint().unwrap();
+
+        assert_eq!(first, second);
+        // 32 bytes as colon separated hex
+        assert_eq!(first.len(), 32 * 3 - 1);
+
+        // the host got another address
+        let moved = Tls::SelfSigned {
+            dir: dir.to_path_buf(),
+            names: vec![String::from("192.168.1.3")],
+        };
+        assert_ne!(moved.load().unwrap().fingerprint().unwrap(), first);
+    }
+}
@@ -0,0 +1,156 @@
+mod fixture;
+
+use common::{ArchiveFormat, JobInfo, JobState, NewArchive, OutputNaming};
+use std::{io::Read, path::PathBuf, time::Duration};
+
+#[tokio::test]
+async fn zip_and_tar_of_a_selection() {
+    let root = std::env::temp_dir().join(format!("webls-archive-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("dir/sub")).unwrap();
+    std::fs::write(root.join("dir/a.txt"), "a".repeat(1000)).unwrap();
+    std::fs::write(root.join("dir/sub/b.mkv"), "b").unwrap();
+    std::fs::write(root.join("c.txt"), "c").unwrap();
+
+    let port = fixture::serve(server::Server::new(root.clone(), root.clone()));
+    let url = |format: &str| {
+        format!("http://127.0.0.1:{port}/archive?format={format}&path=dir&path=c.txt")
+    };
+
+    let res = reqwest::get(url("zip")).await.unwrap();
+    let disposition = res.headers()["content-disposition"].to_str().unwrap();
+    assert!(disposition.contains(".zip"), "{disposition}");
+    let bytes = res.bytes().await.unwrap();
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+    let mut names = zip
+        .file_names()
+        .map(|x| x.unwrap().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        ["c.txt", "dir/", "dir/a.txt", "dir/sub/", "dir/sub/b.mkv"]
+    );
+    let mut a = String::new();
+    zip.by_name("dir/a.txt")
+        .unwrap()
+        .read_to_string(&mut a)
+        .unwrap();
+    assert_eq!(a, "a".repeat(1000));
+
+    let bytes = reqwest::get(url("tar"))
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    let mut tar = tar::Archive::new(bytes.as_ref());
+    let mut files = tar
+        .entries()
+        .unwrap()
+        .map(|x| {
+            let mut x = x.unwrap();
+            let mut content = String::new();
+            x.read_to_string(&mut content).unwrap();
+            (x.path().unwrap().to_path_buf(), content)
+        })
+        .filter(|(_, content)| !content.is_empty())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            (PathBuf::from("c.txt"), "c".to_string()),
+            (PathBuf::from("dir/a.txt"), "a".repeat(1000)),
+            (PathBuf::from("dir/sub/b.mkv"), "b".to_string()),
+        ]
+    );
+
+    // outside of the shared tree
+    let res = reqwest::get(format!("http://127.0.0.1:{port}/archive?path=.."));
+    let res = res.await.unwrap().text().await.unwrap();
+    assert!(res.contains("Forbidden"), "{res}");
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn archive_kept_on_the_server() {
+    let root = std::env::temp_dir().join(format!("webls-pack-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("dir")).unwrap();
+    std::fs::write(root.join("dir/a.txt"), "a".repeat(1000)).unwrap();
+    std::fs::write(root.join("c.txt"), "c").unwrap();
+
+    let port = fixture::serve(server::Server::new(root.clone(), root.clone()));
+    let base = format!("http://127.0.0.1:{port}");
+    let client = reqwest::Client::new();
+    let pack = |to: &str, name: &str| {
+        let new = NewArchive {
+            targets: vec![PathBuf::from("dir"), PathBuf::from("c.txt")],
+            to: PathBuf::from(to),
+            name: name.to_string(),
+            format: ArchiveFormat::TarZst,
+            naming: OutputNaming::Number,
+        };
+        let (client, base) = (client.clone(), base.clone());
+        async move {
+            let res = client
+                .post(format!("{base}/archive"))
+                .json(&new)
+                .send()
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+            let Ok(id) = res.parse::<u64>() else {
+                return Err(res);
+            };
+            loop {
+                let jobs: Vec<JobInfo> = client
+                    .get(format!("{base}/jobs"))
+                    .send()
+                    .await
+                    .unwrap()
+                    .json()
+                    .await
+                    .unwrap();
+                let job = jobs.into_iter().find(|x| x.id == id).unwrap();
+                if job.state != JobState::Running {
+                    assert_eq!(job.percent(), 100);
+                    return Ok(job.state);
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }
+    };
+
+    assert_eq!(pack("", "pack").await, Ok(JobState::Done));
+    assert_eq!(pack("", "pack").await, Ok(JobState::Done));
+    // finished archives are there under their own names and nothing else is left
+    let mut files = std::fs::read_dir(&root)
+        .unwrap()
+        .map(|x| x.unwrap().file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, ["c.txt", "dir", "pack (1).tar.zst", "pack.tar.zst"]);
+    let bytes = std::fs::read(root.join("pack.tar.zst")).unwrap();
+    let tar = zstd::decode_all(bytes.as_slice()).unwrap();
+    let mut names = tar::Archive::new(tar.as_slice())
+        .entries()
+        .unwrap()
+        .map(|x| x.unwrap().path().unwrap().to_path_buf())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            PathBuf::from("c.txt"),
+            PathBuf::from("dir"),
+            PathBuf::from("dir/a.txt")
+        ]
+    );
+
+    // into what is packed, or out of where it should be
+    assert!(pack("dir", "pack").await.unwrap_err().contains("Forbidden"));
+    assert!(pack("", "../pack").await.unwrap_err().contains("Forbidden"));
+    std::fs::remove_dir_all(root).unwrap();
+}
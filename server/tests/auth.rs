@@ -0,0 +1,80 @@
+mod fixture;
+
+use reqwest::{StatusCode, header};
+use std::collections::{HashMap, HashSet};
+
+#[tokio::test]
+async fn basic_auth_users() {
+    let root = std::env::temp_dir().join(format!("webls-auth-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("a.txt"), "a").unwrap();
+
+    let users = HashMap::from([("alice".to_string(), server::hash_password("secret"))]);
+    let port = fixture::serve(server::Server::new(root.clone(), root.clone()).users(users));
+
+    let url = format!("http://127.0.0.1:{port}/download/a.txt");
+    let client = reqwest::Client::new();
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(res.headers().contains_key(header::WWW_AUTHENTICATE));
+
+    let res = client.get(&url).basic_auth("alice", Some("wrong")).send();
+    assert_eq!(res.await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+    let res = client.get(&url).basic_auth("alice", Some("secret")).send();
+    assert_eq!(res.await.unwrap().status(), StatusCode::OK);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn only_admins_mount() {
+    let root = std::env::temp_dir().join(format!("webls-admins-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+
+    let users = HashMap::from([
+        ("alice".to_string(), server::hash_password("secret")),
+        ("bob".to_string(), server::hash_password("secret")),
+    ]);
+    let port = fixture::serve(
+        server::Server::new(root.clone(), root.clone())
+            .users(users)
+            .admins(HashSet::from(["alice".to_string()])),
+    );
+
+    let client = reqwest::Client::new();
+    for path in ["mount", "unmount", "eject"] {
+        let request = |user: &str| {
+            client
+                .post(format!("http://127.0.0.1:{port}/{path}"))
+                .basic_auth(user, Some("secret"))
+                .json("webls-no-such-partition")
+                .send()
+        };
+        let res = request("bob").await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        assert!(res.text().await.unwrap().contains("AdminRequired"));
+
+        // an admin gets as far as looking the partition up
+        let res = request("alice").await.unwrap().text().await.unwrap();
+        assert!(!res.contains("AdminRequired"), "{res}");
+    }
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn everyone_is_admin_without_users() {
+    let root = std::env::temp_dir().join(format!("webls-no-users-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let port = fixture::serve(server::Server::new(root.clone(), root.clone()));
+
+    let res = reqwest::Client::new()
+        .post(format!("http://127.0.0.1:{port}/mount"))
+        .json("webls-no-such-partition")
+        .send()
+        .await
+        .unwrap();
+    assert_ne!(res.status(), StatusCode::FORBIDDEN);
+    let res = res.text().await.unwrap();
+    assert!(!res.contains("AdminRequired"), "{res}");
+    std::fs::remove_dir_all(root).unwrap();
+}
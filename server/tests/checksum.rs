@@ -0,0 +1,87 @@
+mod fixture;
+
+use common::{Checksums, FileChecksum, HashAlgorithm, JobInfo, JobState};
+use serde_json::{Value, json};
+use std::{path::PathBuf, time::Duration};
+
+#[tokio::test]
+async fn checksums_and_verified_copies() {
+    let root = std::env::temp_dir().join(format!("webls-checksum-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("backup")).unwrap();
+    std::fs::write(root.join("footage.mkv"), "abc").unwrap();
+
+    let port = fixture::serve(server::Server::new(root.clone(), root.clone()));
+    let base = format!("http://127.0.0.1:{port}");
+    let client = reqwest::Client::new();
+    let job = |path: &'static str, body: Value| {
+        let request = client.post(format!("{base}{path}")).json(&body);
+        let (client, base) = (client.clone(), base.clone());
+        async move {
+            let id: u64 = request.send().await.unwrap().json().await.unwrap();
+            loop {
+                let jobs: Vec<JobInfo> = client
+                    .get(format!("{base}/jobs"))
+                    .send()
+                    .await
+                    .unwrap()
+                    .json()
+                    .await
+                    .unwrap();
+                let job = jobs.into_iter().find(|x| x.id == id).unwrap();
+                if job.state != JobState::Running {
+                    return job.state;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }
+    };
+
+    let checksums = Checksums {
+        targets: vec![PathBuf::from("footage.mkv")],
+        algorithm: HashAlgorithm::Sha256,
+    };
+    assert_eq!(job("/checksum", json!(checksums)).await, JobState::Done);
+    let known: Vec<FileChecksum> = client
+        .post(format!("{base}/checksums"))
+        .json(&["footage.mkv"])
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        known,
+        [FileChecksum {
+            path: PathBuf::from("footage.mkv"),
+            algorithm: HashAlgorithm::Sha256,
+            hex: String::from("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        }]
+    );
+
+    let copy = (vec![PathBuf::from("footage.mkv")], PathBuf::from("backup"));
+    client
+        .post(format!("{base}/cp"))
+        .json(&copy)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(job("/verify", json!(copy)).await, JobState::Done);
+
+    std::fs::write(root.join("backup/footage.mkv"), "abd!").unwrap();
+    let broken = job("/verify", json!(copy)).await;
+    assert!(
+        matches!(&broken, JobState::Failed(x) if x.contains("ChecksumMismatch")),
+        "{broken:?}"
+    );
+
+    // directories are refused before any job starts
+    let dir = Checksums {
+        targets: vec![PathBuf::from("backup")],
+        algorithm: HashAlgorithm::Blake3,
+    };
+    let res = client.post(format!("{base}/checksum")).json(&dir).send();
+    let res = res.await.unwrap().text().await.unwrap();
+    assert!(res.contains("NotAFile"), "{res}");
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,124 @@
+mod fixture;
+
+use common::{Extraction, JobInfo, JobState, OutputNaming};
+use std::{io::Write, path::PathBuf, time::Duration};
+
+fn tar_with(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut tar = tar::Builder::new(Vec::new());
+    for (name, data) in entries {
+        let mut header = tar::Header::new_gnu();
+        // written as is, `set_path` refuses the names archives should not hold
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(data.len() as u64);
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append(&header, *data).unwrap();
+    }
+    tar.into_inner().unwrap()
+}
+
+#[tokio::test]
+async fn archives_unpack_safely() {
+    let root = std::env::temp_dir().join(format!("webls-extract-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("out")).unwrap();
+
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(&tar_with(&[("docs/a.txt", b"a")])).unwrap();
+    std::fs::write(root.join("bundle.tar.gz"), gz.finish().unwrap()).unwrap();
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("z.txt", zip::write::SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(b"z").unwrap();
+    let zip = zip.finish().unwrap().into_inner();
+    std::fs::write(root.join("bundle.zip"), zip).unwrap();
+
+    std::fs::write(root.join("evil.tar"), tar_with(&[("../evil.txt", b"e")])).unwrap();
+    let zeros = vec![0; 10 * 1024 * 1024];
+    let bomb = zstd::encode_all(tar_with(&[("zeros", &zeros)]).as_slice(), 19).unwrap();
+    std::fs::write(root.join("bomb.tar.zst"), bomb).unwrap();
+
+    let port = fixture::serve(server::Server::new(root.clone(), root.clone()));
+    let base = format!("http://127.0.0.1:{port}");
+    let client = reqwest::Client::new();
+    let extract = |archive: &str, naming: OutputNaming| {
+        let extraction = Extraction {
+            archives: vec![PathBuf::from(archive)],
+            to: PathBuf::from("out"),
+            naming,
+        };
+        let (client, base) = (client.clone(), base.clone());
+        async move {
+            let id: u64 = client
+                .post(format!("{base}/extract"))
+                .json(&extraction)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            loop {
+                let jobs: Vec<JobInfo> = client
+                    .get(format!("{base}/jobs"))
+                    .send()
+                    .await
+                    .unwrap()
+                    .json()
+                    .await
+                    .unwrap();
+                let job = jobs.into_iter().find(|x| x.id == id).unwrap();
+                if job.state != JobState::Running {
+                    return job.state;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }
+    };
+
+    assert_eq!(
+        extract("bundle.tar.gz", OutputNaming::Keep).await,
+        JobState::Done
+    );
+    assert_eq!(std::fs::read(root.join("out/docs/a.txt")).unwrap(), b"a");
+    assert!(matches!(
+        extract("bundle.tar.gz", OutputNaming::Keep).await,
+        JobState::Failed(_)
+    ));
+    assert_eq!(
+        extract("bundle.tar.gz", OutputNaming::Number).await,
+        JobState::Done
+    );
+    assert!(root.join("out/docs/a (1).txt").exists());
+
+    assert_eq!(
+        extract("bundle.zip", OutputNaming::Keep).await,
+        JobState::Done
+    );
+    assert_eq!(std::fs::read(root.join("out/z.txt")).unwrap(), b"z");
+
+    // overwriting replaces a symlink instead of writing where it leads
+    let outside = root.with_file_name(format!("webls-extract-outside-{}", std::process::id()));
+    std::fs::write(&outside, "untouched").unwrap();
+    std::fs::remove_file(root.join("out/z.txt")).unwrap();
+    std::os::unix::fs::symlink(&outside, root.join("out/z.txt")).unwrap();
+    assert_eq!(
+        extract("bundle.zip", OutputNaming::Overwrite).await,
+        JobState::Done
+    );
+    assert_eq!(std::fs::read(&outside).unwrap(), b"untouched");
+    assert!(!root.join("out/z.txt").is_symlink());
+    assert_eq!(std::fs::read(root.join("out/z.txt")).unwrap(), b"z");
+    std::fs::remove_file(outside).unwrap();
+
+    let evil = extract("evil.tar", OutputNaming::Keep).await;
+    assert!(matches!(&evil, JobState::Failed(x) if x.contains("Forbidden")));
+    assert!(!root.join("evil.txt").exists());
+
+    let bomb = extract("bomb.tar.zst", OutputNaming::Keep).await;
+    assert!(matches!(&bomb, JobState::Failed(x) if x.contains("ExtractLimit")));
+    assert!(!root.join("out/zeros").exists());
+
+    std::fs::remove_dir_all(root).unwrap();
+}
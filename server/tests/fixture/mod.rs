@@ -0,0 +1,10 @@
+/// starts `server` on a port of its own and returns it
+///
+/// the listener is bound before the server starts, so tests running in parallel never get handed
+/// the same free port and requests made right away wait in the backlog instead of failing
+pub fn serve(server: server::Server) -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(server.listener(listener).serve());
+    port
+}
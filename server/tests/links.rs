@@ -0,0 +1,146 @@
+mod fixture;
+
+use common::{LinkInfo, NewLink, PublicLink, Unit};
+use reqwest::{StatusCode, header};
+use std::{collections::HashMap, path::PathBuf};
+
+#[tokio::test]
+async fn share_link_lifetime() {
+    let root = std::env::temp_dir().join(format!("webls-links-{}", std::process::id()));
+    let (site, cache) = (root.with_extension("site"), root.with_extension("cache"));
+    std::fs::create_dir_all(&site).unwrap();
+    std::fs::write(site.join("index.html"), "<html></html>").unwrap();
+    std::fs::create_dir_all(root.join("docs")).unwrap();
+    std::fs::write(root.join("docs/a.txt"), "abc").unwrap();
+    std::fs::write(root.join("secret.txt"), "secret").unwrap();
+
+    let users = HashMap::from([("alice".to_string(), server::hash_password("secret"))]);
+    let port = fixture::serve(
+        server::Server::new(site.clone(), root.clone())
+            .users(users)
+            .cache(cache.clone()),
+    );
+    let base = format!("http://127.0.0.1:{port}");
+    let client = reqwest::Client::new();
+
+    let res = client.get(format!("{base}/links")).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let link: LinkInfo = client
+        .post(format!("{base}/links"))
+        .basic_auth("alice", Some("secret"))
+        .json(&NewLink {
+            path: "docs".into(),
+            expires_in: 60 * 60,
+            password: Some("pw".into()),
+            max_downloads: Some(1),
+        })
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let at = format!("{base}/s/{}", link.token);
+
+    // the page loads without logging in, like the link routes
+    let res = client.get(&at).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "<html></html>");
+    let public: PublicLink = client
+        .get(format!("{at}/link"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(public.name, "docs");
+    assert!(public.dir && public.locked);
+
+    let ls = client.post(format!("{at}/ls")).json(&PathBuf::new()).send();
+    let ls = ls.await.unwrap();
+    assert_eq!(ls.status(), StatusCode::UNAUTHORIZED);
+    let ls = ls.text().await.unwrap();
+    assert!(ls.contains("Locked"), "{ls}");
+
+    let res = client.post(format!("{at}/unlock")).json("wrong").send();
+    let res = res.await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(res.headers().get(header::SET_COOKIE).is_none());
+    let res = client.post(format!("{at}/unlock")).json("pw").send();
+    let cookie = res.await.unwrap().headers()[header::SET_COOKIE]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+
+    let units: Vec<Unit> = client
+        .post(format!("{at}/ls"))
+        .header(header::COOKIE, &cookie)
+        .json(&PathBuf::new())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(units.len(), 1);
+    assert_eq!(units[0].path, PathBuf::from("a.txt"));
+
+    // what is stored of the password is no key to the link
+    let stored: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(cache.join("links.json")).unwrap()).unwrap();
+    let stored = stored[0]["password"].as_str().unwrap();
+    assert!(!cookie.contains(stored), "{cookie}");
+    let forged = client
+        .post(format!("{at}/ls"))
+        .header(header::COOKIE, format!("webls_link={stored}"))
+        .json(&PathBuf::new())
+        .send();
+    assert_eq!(forged.await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+    // nothing outside the shared directory
+    let res = client
+        .post(format!("{at}/ls"))
+        .header(header::COOKIE, &cookie)
+        .json(&PathBuf::from(".."))
+        .send();
+    assert!(
+        res.await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+            .contains("Forbidden")
+    );
+
+    let download = |range: &str| {
+        client
+            .get(format!("{at}/download/a.txt"))
+            .header(header::COOKIE, &cookie)
+            .header(header::RANGE, range)
+            .send()
+    };
+    // the rest of a download is not another one
+    let res = download("bytes=1-").await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "bc");
+    // a range from the start is the whole file all the same
+    let res = download("bytes=0-").await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "abc");
+    // the only download allowed is spent
+    let again = download("bytes=1-").await.unwrap();
+    assert_eq!(again.status(), StatusCode::GONE);
+    let again = again.text().await.unwrap();
+    assert!(again.contains("LinkNotFound"), "{again}");
+
+    let res = client.get(format!("{base}/s/nothing/link")).send();
+    let res = res.await.unwrap();
+    assert_eq!(res.status(), StatusCode::GONE);
+    assert!(res.text().await.unwrap().contains("LinkNotFound"));
+
+    std::fs::remove_dir_all(root).unwrap();
+    std::fs::remove_dir_all(site).unwrap();
+    std::fs::remove_dir_all(cache).unwrap();
+}
@@ -0,0 +1,143 @@
+mod fixture;
+
+use reqwest::{
+    StatusCode,
+    multipart::{Form, Part},
+};
+use server::Quotas;
+use std::{collections::HashMap, path::PathBuf};
+
+/// serves a fresh directory holding an empty `small` directory to `users`, all with the password
+/// `secret`, and returns the base url
+async fn serve(name: &str, quotas: Quotas, users: &[&str]) -> (String, PathBuf) {
+    let root = std::env::temp_dir().join(format!("webls-quota-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("small")).unwrap();
+
+    let users = users
+        .iter()
+        .map(|x| (x.to_string(), server::hash_password("secret")))
+        .collect::<HashMap<_, _>>();
+    let port = fixture::serve(
+        server::Server::new(root.clone(), root.clone())
+            .cache(root.join(".cache"))
+            .state(root.join(".state"))
+            .quotas(quotas)
+            .users(users),
+    );
+    let base = format!("http://127.0.0.1:{port}");
+    (base, root)
+}
+
+/// the status and body of the answer to uploading `form` as `user`
+async fn upload(base: &str, user: Option<&str>, form: Form) -> (StatusCode, String) {
+    let mut request = reqwest::Client::new()
+        .post(format!("{base}/upload"))
+        .multipart(form);
+    if let Some(user) = user {
+        request = request.basic_auth(user, Some("secret"));
+    }
+    let res = request.send().await.unwrap();
+    (res.status(), res.text().await.unwrap())
+}
+
+async fn uploaded(base: &str, user: Option<&str>, form: Form) {
+    assert_eq!(
+        upload(base, user, form).await,
+        (StatusCode::OK, String::new())
+    );
+}
+
+async fn refused(base: &str, user: Option<&str>, form: Form) {
+    let (status, res) = upload(base, user, form).await;
+    assert_eq!(status, StatusCode::INSUFFICIENT_STORAGE, "{res}");
+    assert!(res.contains("InsufficientStorage"), "{res}");
+}
+
+/// browsers send the path as the field name as is
+fn form() -> Form {
+    Form::new().percent_encode_noop()
+}
+
+fn file(path: &str, len: usize) -> Form {
+    form().part(path.to_string(), Part::bytes(vec![7; len]))
+}
+
+/// a part without a length, so only the streaming check can catch it
+fn streamed(path: &str, len: usize) -> Form {
+    let chunks = (0..len / 100).map(|_| Ok::<_, std::io::Error>(vec![7u8; 100]));
+    let body = reqwest::Body::wrap_stream(futures_util::stream::iter(chunks));
+    form().part(path.to_string(), Part::stream(body))
+}
+
+fn files_in(dir: PathBuf) -> Vec<String> {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|x| x.unwrap().file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn directory_quota() {
+    let quotas = Quotas::default().dir("small".into(), 1000);
+    let (base, root) = serve("dir", quotas, &[]).await;
+
+    uploaded(&base, None, file("small/fits.bin", 500)).await;
+    refused(&base, None, file("small/big.bin", 2000)).await;
+    refused(&base, None, streamed("small/streamed.bin", 2000)).await;
+    // the multipart framing around the file does not count against the quota
+    uploaded(&base, None, file("small/exact.bin", 500)).await;
+    let mut files = files_in(root.join("small"));
+    files.sort();
+    assert_eq!(files, ["exact.bin", "fits.bin"]);
+
+    // other directories are not limited
+    uploaded(&base, None, file("big.bin", 2000)).await;
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn user_quota() {
+    let quotas = Quotas::default().user("alice".into(), 1000);
+    let (base, root) = serve("user", quotas, &["alice", "bob"]).await;
+
+    uploaded(&base, Some("alice"), file("a.bin", 600)).await;
+    refused(&base, Some("alice"), streamed("b.bin", 600)).await;
+    uploaded(&base, Some("bob"), file("c.bin", 600)).await;
+
+    // moved uploads still count, deleted ones stop counting
+    let res = reqwest::Client::new()
+        .post(format!("{base}/mv"))
+        .basic_auth("alice", Some("secret"))
+        .json(&(vec![PathBuf::from("a.bin")], PathBuf::from("small")))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    refused(&base, Some("alice"), streamed("b.bin", 600)).await;
+    std::fs::remove_file(root.join("small/a.bin")).unwrap();
+    uploaded(&base, Some("alice"), file("b.bin", 600)).await;
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn user_quota_needs_a_login() {
+    // without users nobody is logged in, so claiming to be alice earns no quota of hers
+    let quotas = Quotas::default().user("alice".into(), 1000);
+    let (base, root) = serve("anonymous", quotas, &[]).await;
+
+    uploaded(&base, Some("alice"), file("a.bin", 600)).await;
+    uploaded(&base, Some("alice"), file("b.bin", 600)).await;
+    assert!(!root.join(".state/quotas/users.json").exists());
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn minimum_free_space() {
+    let quotas = Quotas::default().min_free(u64::MAX);
+    let (base, root) = serve("free", quotas, &[]).await;
+
+    refused(&base, None, file("any.bin", 10)).await;
+    assert!(!root.join("any.bin").exists());
+    std::fs::remove_dir_all(root).unwrap();
+}
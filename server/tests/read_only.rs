@@ -0,0 +1,47 @@
+mod fixture;
+
+use common::Settings;
+use std::path::PathBuf;
+
+#[tokio::test]
+async fn mutating_routes_are_gone() {
+    let root = std::env::temp_dir().join(format!("webls-read-only-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("a.txt"), "a").unwrap();
+
+    let port = fixture::serve(server::Server::new(root.clone(), root.clone()).read_only(true));
+    let base = format!("http://127.0.0.1:{port}");
+    let client = reqwest::Client::new();
+
+    let settings: Settings = reqwest::get(format!("{base}/settings"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(settings.read_only);
+
+    let res = client
+        .post(format!("{base}/mkdir"))
+        .json(&PathBuf::from("new"))
+        .send()
+        .await
+        .unwrap();
+    assert!(!res.status().is_success());
+    assert!(!root.join("new").exists());
+
+    let res = client
+        .post(format!("{base}/rm"))
+        .json(&serde_json::json!([{ "path": "a.txt", "kind": "File" }]))
+        .send()
+        .await
+        .unwrap();
+    assert!(!res.status().is_success());
+    assert!(root.join("a.txt").exists());
+
+    let a = reqwest::get(format!("{base}/download/a.txt"))
+        .await
+        .unwrap();
+    assert_eq!(a.text().await.unwrap(), "a");
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,71 @@
+mod fixture;
+
+use common::Unit;
+use reqwest::StatusCode;
+use server::Share;
+use std::path::PathBuf;
+
+/// a root share holding `a.txt` and a read only `media` share holding `b.txt`
+async fn serve() -> (String, PathBuf) {
+    let root = std::env::temp_dir().join(format!("webls-shares-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("root")).unwrap();
+    std::fs::create_dir_all(root.join("media")).unwrap();
+    std::fs::write(root.join("root/a.txt"), "a").unwrap();
+    std::fs::write(root.join("media/b.txt"), "b").unwrap();
+
+    let port = fixture::serve(
+        server::Server::new(root.clone(), root.join("root"))
+            .share(Share::new("media".into(), root.join("media")).read_only(true))
+            .cache(root.join(".cache")),
+    );
+    (format!("http://127.0.0.1:{port}"), root)
+}
+
+async fn ls(base: &str, path: &str) -> Vec<PathBuf> {
+    let units: Vec<Unit> = reqwest::Client::new()
+        .post(format!("{base}/ls"))
+        .json(&PathBuf::from(path))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let mut paths = units.into_iter().map(|x| x.path).collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+#[tokio::test]
+async fn named_shares() {
+    let (base, root) = serve().await;
+
+    assert_eq!(
+        ls(&base, "").await,
+        [PathBuf::from("a.txt"), "media".into()]
+    );
+    assert_eq!(ls(&base, "media").await, [PathBuf::from("media/b.txt")]);
+    let b = reqwest::get(format!("{base}/download/media/b.txt")).await;
+    assert_eq!(b.unwrap().text().await.unwrap(), "b");
+
+    let res = reqwest::Client::new()
+        .post(format!("{base}/mkdir"))
+        .json(&PathBuf::from("media/new"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = res.text().await.unwrap();
+    assert!(res.contains("ReadOnly"), "{res}");
+    assert!(!root.join("media/new").exists());
+
+    let res = reqwest::get(format!("{base}/download/media/..%2Froot%2Fa.txt"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert!(res.text().await.unwrap().contains("Forbidden"));
+
+    let res = reqwest::get(format!("{base}/download/nowhere/a.txt")).await;
+    assert_eq!(res.unwrap().status(), StatusCode::NOT_FOUND);
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,182 @@
+mod fixture;
+
+use reqwest::{StatusCode, header};
+use std::path::PathBuf;
+
+const LEN: usize = 3 * 1024 * 1024 + 17;
+
+fn content() -> Vec<u8> {
+    (0..LEN).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+/// serves a fresh directory holding `big.mp4` and returns the base url
+async fn serve(name: &str) -> (String, PathBuf) {
+    let root = std::env::temp_dir().join(format!("webls-stream-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("big.mp4"), content()).unwrap();
+
+    let port = fixture::serve(server::Server::new(root.clone(), root.clone()));
+    let base = format!("http://127.0.0.1:{port}");
+    (base, root)
+}
+
+#[tokio::test]
+async fn full_download_has_length_and_mime() {
+    let (base, root) = serve("full").await;
+    let res = reqwest::get(format!("{base}/download/big.mp4"))
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "video/mp4");
+    assert_eq!(res.headers()[header::ACCEPT_RANGES], "bytes");
+    assert_eq!(res.headers()[header::CONTENT_LENGTH], LEN.to_string());
+    assert_eq!(res.bytes().await.unwrap(), content());
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn single_range_is_partial_content() {
+    let (base, root) = serve("single").await;
+    let start = 2 * 1024 * 1024;
+    let res = reqwest::Client::new()
+        .get(format!("{base}/download/big.mp4"))
+        .header(header::RANGE, format!("bytes={start}-"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        res.headers()[header::CONTENT_RANGE],
+        format!("bytes {start}-{}/{LEN}", LEN - 1)
+    );
+    assert_eq!(
+        res.headers()[header::CONTENT_LENGTH],
+        (LEN - start).to_string()
+    );
+    assert_eq!(res.bytes().await.unwrap(), content()[start..]);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn multiple_ranges_are_multipart() {
+    let (base, root) = serve("multiple").await;
+    let res = reqwest::Client::new()
+        .get(format!("{base}/download/big.mp4"))
+        .header(header::RANGE, "bytes=0-9, -5")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    let content_type = res.headers()[header::CONTENT_TYPE].to_str().unwrap();
+    assert!(content_type.starts_with("multipart/byteranges; boundary="));
+    let length = res.headers()[header::CONTENT_LENGTH].clone();
+    let body = res.bytes().await.unwrap();
+    assert_eq!(length, body.len().to_string());
+
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains("Content-Range: bytes 0-9/"));
+    assert!(body.contains(&format!(
+        "Content-Range: bytes {}-{}/{LEN}",
+        LEN - 5,
+        LEN - 1
+    )));
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn unsatisfiable_range() {
+    let (base, root) = serve("unsatisfiable").await;
+    let res = reqwest::Client::new()
+        .get(format!("{base}/download/big.mp4"))
+        .header(header::RANGE, format!("bytes={LEN}-"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(
+        res.headers()[header::CONTENT_RANGE],
+        format!("bytes */{LEN}")
+    );
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn conditional_requests() {
+    let (base, root) = serve("conditional").await;
+    let url = format!("{base}/download/big.mp4");
+    let client = reqwest::Client::new();
+    let first = client.get(&url).send().await.unwrap();
+    let etag = first.headers()[header::ETAG].clone();
+    let modified = first.headers()[header::LAST_MODIFIED].clone();
+
+    let res = client
+        .get(&url)
+        .header(header::IF_NONE_MATCH, etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    let res = client
+        .get(&url)
+        .header(header::IF_MODIFIED_SINCE, modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+    let res = client
+        .get(&url)
+        .header(header::RANGE, "bytes=0-9")
+        .header(header::IF_RANGE, etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+
+    let res = client
+        .get(&url)
+        .header(header::RANGE, "bytes=0-9")
+        .header(header::IF_RANGE, "\"stale\"")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.bytes().await.unwrap().len(), LEN);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn missing_file_is_not_found() {
+    let (base, root) = serve("missing").await;
+    let res = reqwest::get(format!("{base}/download/nothing.mp4"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn paths_out_of_the_tree_are_refused() {
+    let (base, root) = serve("outside").await;
+    let secret = root.with_file_name(format!("webls-secret-{}", std::process::id()));
+    std::fs::write(&secret, "not for clients").unwrap();
+    let name = secret.file_name().unwrap().to_str().unwrap();
+    let absolute = secret.to_str().unwrap().replace('/', "%2F");
+
+    // encoded, or the client would resolve the dots itself
+    for path in [format!("..%2F{name}"), absolute] {
+        let res = reqwest::get(format!("{base}/download/{path}"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN, "{path}");
+        let body = res.text().await.unwrap();
+        assert!(!body.contains("not for clients"), "{path} : {body}");
+    }
+    std::fs::remove_file(secret).unwrap();
+    std::fs::remove_dir_all(root).unwrap();
+}
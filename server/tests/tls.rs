@@ -0,0 +1,31 @@
+mod fixture;
+
+use server::Tls;
+
+#[tokio::test]
+async fn self_signed_https() {
+    let root = std::env::temp_dir().join(format!("webls-https-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("a.txt"), "a").unwrap();
+    let tls = Tls::SelfSigned {
+        dir: root.join(".tls"),
+        names: vec![String::from("localhost")],
+    };
+    let fingerprint = tls.load().unwrap().fingerprint().unwrap();
+
+    let port = fixture::serve(server::Server::new(root.clone(), root.clone()).tls(tls.clone()));
+
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let res = client
+        .get(format!("https://localhost:{port}/download/a.txt"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "a");
+    // the certificate made for the first run is the one served
+    assert_eq!(tls.load().unwrap().fingerprint().unwrap(), fingerprint);
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,58 @@
+use common::{GlobalState, GlobalStateStoreFields, ServerEvent, Store, EVENTS_PATH};
+use leptos::prelude::*;
+use std::time::Duration;
+use web_sys::{
+    wasm_bindgen::{closure::Closure, JsCast},
+    EventSource, MessageEvent,
+};
+
+use crate::files_box::origin_with;
+
+const NOTICE_TIME: Duration = Duration::from_secs(5);
+
+fn notice(event: &ServerEvent) -> String {
+    match event {
+        ServerEvent::DriveAdded { name, at: Some(_) } => format!("{name} is now shared"),
+        ServerEvent::DriveAdded { name, at: None } => format!("{name} was plugged in"),
+        ServerEvent::DriveRemoved { name } => format!("{name} was removed"),
+    }
+}
+
+/// listens to the server's events, refreshes the listing and shows what happened for a moment
+#[component]
+pub fn Notices() -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let notices = RwSignal::new(Vec::<(u64, String)>::new());
+    let next = StoredValue::new(0u64);
+
+    if let Ok(source) = EventSource::new(&origin_with(EVENTS_PATH)) {
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |ev: MessageEvent| {
+            let Some(event) = ev
+                .data()
+                .as_string()
+                .and_then(|x| serde_json::from_str::<ServerEvent>(&x).ok())
+            else {
+                return;
+            };
+            store.units_refetch_tick().update(|x| *x = !*x);
+            let id = next.get_value();
+            next.set_value(id + 1);
+            notices.update(|xs| xs.push((id, notice(&event))));
+            set_timeout(
+                move || notices.update(|xs| xs.retain(|(x, _)| *x != id)),
+                NOTICE_TIME,
+            );
+        });
+        source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        // the app lives as long as the page, and so does its event source
+        on_message.forget();
+    }
+
+    view! {
+        <ul class="fixed bottom-5 left-1/2 -translate-x-1/2 grid gap-2 z-30">
+            <For each=move || notices.get() key=|(id, _)| *id let:notice>
+                <li class="bg-black text-white rounded-lg px-4 py-2 text-xl">{notice.1}</li>
+            </For>
+        </ul>
+    }
+}
@@ -1,25 +1,61 @@
 use std::{
+    collections::HashMap,
     ffi::OsStr,
     path::{Path, PathBuf},
 };
 
 use crate::Unit;
 use atoms::{BaseIcon, Icon, IconSize};
-use common::{GlobalState, GlobalStateStoreFields, SelectedState, LS_PATH, MKDIR_PATH};
-use common::{Store, UnitKind};
+use common::{thumbnailable, Store, UnitKind};
+use common::{
+    ArchiveFormat, GlobalState, GlobalStateStoreFields, MediaInfo, SelectedState, Settings,
+    ShareInfo, ARCHIVE_PATH, LINK_PREFIX, LS_PATH, MKDIR_PATH, POSTER_PATH, PREVIEW_PATH,
+    PROBE_PATH, SETTINGS_PATH, SHARES_PATH, THUMBNAIL_PATH,
+};
 use leptos::{either::Either, ev, html::Ol, prelude::*};
 use leptos_router::hooks::{use_navigate, use_query_map};
 use leptos_use::{use_event_listener, use_window};
-use web_sys::KeyboardEvent;
+use web_sys::{wasm_bindgen::JsCast, KeyboardEvent, MouseEvent};
+
+/// `/s/{token}` when the page is a share link, the api and the navigation stay under it
+pub fn link() -> Option<String> {
+    let path = window().location().pathname().ok()?;
+    let token = path.strip_prefix(LINK_PREFIX)?.strip_prefix('/')?;
+    let token = token.split('/').next().filter(|x| !x.is_empty())?;
+    Some(format!("{LINK_PREFIX}/{token}"))
+}
 
 pub fn origin_with(rel: &str) -> String {
+    let link = link().unwrap_or_default();
     window()
         .location()
         .origin()
-        .map(|x| format!("{x}{rel}"))
+        .map(|x| format!("{x}{link}{rel}"))
         .unwrap()
 }
 
+pub fn media_url(endpoint: &str, path: &Path) -> String {
+    reqwest::Url::parse_with_params(
+        &origin_with(endpoint),
+        [("path", path.to_str().unwrap_or_default())],
+    )
+    .map(String::from)
+    .unwrap_or_default()
+}
+
+/// where the server streams `paths` packed in one archive
+pub fn archive_url(format: ArchiveFormat, paths: &[PathBuf]) -> String {
+    let paths = paths
+        .iter()
+        .map(|x| ("path", x.to_str().unwrap_or_default()));
+    reqwest::Url::parse_with_params(
+        &origin_with(ARCHIVE_PATH),
+        [("format", format.extension())].into_iter().chain(paths),
+    )
+    .map(String::from)
+    .unwrap_or_default()
+}
+
 pub async fn ls(base: PathBuf) -> Result<Vec<Unit>, String> {
     let url = origin_with(LS_PATH);
     let res = reqwest::Client::new()
@@ -34,12 +70,71 @@ pub async fn ls(base: PathBuf) -> Result<Vec<Unit>, String> {
     Ok(res)
 }
 
+pub async fn shares() -> Result<Vec<ShareInfo>, String> {
+    reqwest::Client::new()
+        .get(origin_with(SHARES_PATH))
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<Vec<ShareInfo>>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+pub async fn settings() -> Result<Settings, String> {
+    reqwest::Client::new()
+        .get(origin_with(SETTINGS_PATH))
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<Settings>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+pub async fn probe(targets: Vec<PathBuf>) -> Result<Vec<Option<MediaInfo>>, String> {
+    // share links do not run ffprobe for whoever holds them
+    if targets.is_empty() || link().is_some() {
+        return Ok(Vec::new());
+    }
+    reqwest::Client::new()
+        .post(origin_with(PROBE_PATH))
+        .json(&targets)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<Vec<Option<MediaInfo>>>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+/// media info of every video and audio unit, keyed by path
+pub async fn probe_units(units: Vec<Unit>) -> HashMap<PathBuf, MediaInfo> {
+    let paths = units
+        .into_iter()
+        .filter(|x| matches!(x.kind, UnitKind::Video | UnitKind::Audio))
+        .map(|x| x.path)
+        .collect::<Vec<_>>();
+    match probe(paths.clone()).await {
+        Ok(infos) => paths
+            .into_iter()
+            .zip(infos)
+            .filter_map(|(path, info)| Some((path, info?)))
+            .collect(),
+        Err(err) => {
+            leptos::logging::error!("probe Error : {err}");
+            HashMap::new()
+        }
+    }
+}
+
 #[component]
 pub fn FilesBox(
     drop_zone_el: NodeRef<Ol>,
     is_over_drop_zone: Signal<bool>,
     current_path: RwSignal<PathBuf>,
     units: Memo<Option<Vec<Unit>>>,
+    probes: Signal<HashMap<PathBuf, MediaInfo>>,
 ) -> impl IntoView {
     let query = use_query_map();
     let store: Store<GlobalState> = use_context().unwrap();
@@ -80,9 +175,12 @@ pub fn FilesBox(
         };
     });
 
+    let gallery = move || store.gallery().get();
+
     view! {
         <ol
             class="w-full min-h-80 m-5 p-5 border-2 border-lime-500 rounded-lg"
+            class=(["grid", "grid-cols-[repeat(auto-fill,minmax(13rem,1fr))]", "gap-2"], gallery)
             node_ref=drop_zone_el
         >
             <li>
@@ -91,7 +189,7 @@ pub fn FilesBox(
             {
                 move || units.get().map(|xs| {
                     xs.into_iter().map(|x| {view! {
-                        <UnitComp unit=x is_over_drop_zone />
+                        <UnitComp unit=x is_over_drop_zone probes />
                     }}).collect_view()
                 })
             }
@@ -148,7 +246,7 @@ fn Mkdir(current_path: RwSignal<PathBuf>) -> impl IntoView {
     }
 }
 
-fn path_as_query(path: &Path) -> String {
+pub fn path_as_query(path: &Path) -> String {
     let mut it = path.iter();
     let kv = |(i, x): (_, &OsStr)| format!("{}={}", i, x.to_str().unwrap());
 
@@ -173,7 +271,11 @@ fn path_as_query_test() {
 }
 
 #[component]
-fn UnitComp(unit: Unit, is_over_drop_zone: Signal<bool>) -> impl IntoView {
+fn UnitComp(
+    unit: Unit,
+    is_over_drop_zone: Signal<bool>,
+    probes: Signal<HashMap<PathBuf, MediaInfo>>,
+) -> impl IntoView {
     let navigate = use_navigate();
     let store = use_context::<Store<GlobalState>>().unwrap();
 
@@ -189,6 +291,9 @@ fn UnitComp(unit: Unit, is_over_drop_zone: Signal<bool>) -> impl IntoView {
             UnitKind::Video | UnitKind::Audio => {
                 *store.media_play().write() = Some(unit.clone());
             }
+            UnitKind::Image => {
+                *store.image_view().write() = Some(unit.clone());
+            }
             UnitKind::File => {
                 unit.click_anchor();
                 store.select().write().remove_unit(&unit);
@@ -204,6 +309,14 @@ fn UnitComp(unit: Unit, is_over_drop_zone: Signal<bool>) -> impl IntoView {
     };
 
     let name = unit.name();
+    let read_only = {
+        let unit = unit.clone();
+        move || store.with(|x| x.share_of(&unit).is_some_and(|x| x.read_only))
+    };
+    let duration = {
+        let path = unit.path.clone();
+        move || probes.with(|x| x.get(&path).and_then(|x| x.duration_text()))
+    };
     let icon = {
         let unit = unit.clone();
         move || {
@@ -216,27 +329,41 @@ fn UnitComp(unit: Unit, is_over_drop_zone: Signal<bool>) -> impl IntoView {
                 SelectedState::Copy if is_selected => {
                     Either::Right(Either::Right(view! { <Icon src="copy" /> }))
                 }
-                _ => Either::Left(view! { <UnitIcon unit=unit.clone() is_over_drop_zone /> }),
+                _ => {
+                    let gallery = store.gallery().get();
+                    Either::Left(view! { <UnitIcon unit=unit.clone() is_over_drop_zone gallery /> })
+                }
             }
         }
     };
+    let gallery = move || store.gallery().get();
 
     view! {
         <li>
             <button
                 on:dblclick=ondblclick
                 on:click=onclick
-                class="grid grid-cols-2 hover:text-white hover:bg-black justify-items-left"
+                class="grid hover:text-white hover:bg-black"
+                class=(["grid-cols-2", "justify-items-left"], move || !gallery())
+                class=(["grid-cols-1", "justify-items-center", "w-full"], gallery)
             >
                 {icon}
-                <span class="mx-0 px-0 py-5">{name}</span>
+                <span class="mx-0 px-0 py-5" class:truncate=gallery class:max-w-full=gallery>
+                    {name}
+                </span>
+                {move || duration().map(|x| view! {
+                    <span class="text-sm text-gray-500 px-1">{x}</span>
+                })}
+                <Show when=read_only>
+                    <span class="text-sm text-gray-500 px-1">"read only"</span>
+                </Show>
             </button>
         </li>
     }
 }
 
 #[component]
-fn UnitIcon(unit: Unit, is_over_drop_zone: Signal<bool>) -> impl IntoView {
+fn UnitIcon(unit: Unit, is_over_drop_zone: Signal<bool>, gallery: bool) -> impl IntoView {
     let store = use_context::<Store<GlobalState>>().unwrap();
 
     let href = origin_with(&format!(
@@ -247,7 +374,7 @@ fn UnitIcon(unit: Unit, is_over_drop_zone: Signal<bool>) -> impl IntoView {
         <a
             id=unit.name()
             download=unit.name()
-            href={href}
+            href=href.clone()
             hidden
         ></a>
     });
@@ -255,19 +382,90 @@ fn UnitIcon(unit: Unit, is_over_drop_zone: Signal<bool>) -> impl IntoView {
     let size = move || {
         if is_over_drop_zone.get() {
             IconSize::Small
+        } else if gallery {
+            IconSize::Large
         } else {
             IconSize::default()
         }
     };
-    view! {
+    let remote = match unit.kind {
+        UnitKind::Image if gallery && thumbnailable(&unit.path) => {
+            Some(media_url(THUMBNAIL_PATH, &unit.path))
+        }
+        // svg and alike, the browser draws them from the original
+        UnitKind::Image if gallery => Some(href.clone()),
+        UnitKind::Video if gallery => Some(media_url(POSTER_PATH, &unit.path)),
+        _ => None,
+    };
+    let scrub = (gallery && unit.kind == UnitKind::Video).then(|| unit.path.clone());
+    let icon = view! {
         <BaseIcon
+            remote
             src={
-                let kind = unit.kind.clone();
-                move || kind.to_string()
+                let unit = unit.clone();
+                move || match store.with(|x| x.share_of(&unit).is_some()) {
+                    true => String::from("share"),
+                    false => unit.kind.to_string(),
+                }
             }
             active=move || !store.select().read().is_selected(&unit)
             size
         />
+    };
+    let icon = match scrub {
+        Some(path) => Either::Left(view! {
+            <div class="relative">
+                {icon}
+                <ScrubPreview path />
+            </div>
+        }),
+        None => Either::Right(icon),
+    };
+    view! {
+        {icon}
         {download_link}
     }
 }
+
+const PREVIEW_FRAMES: u32 = 10;
+
+/// shows the frame of the video preview strip under the cursor while hovering the poster
+#[component]
+fn ScrubPreview(path: PathBuf) -> impl IntoView {
+    let frame = RwSignal::new(None::<u32>);
+    let sprite = format!("{}&frames={PREVIEW_FRAMES}", media_url(PREVIEW_PATH, &path));
+
+    let on_move = move |ev: MouseEvent| {
+        let width = ev
+            .current_target()
+            .unwrap()
+            .unchecked_into::<web_sys::Element>()
+            .client_width();
+        if width <= 0 {
+            return;
+        }
+        let at = ev.offset_x().clamp(0, width - 1) as u32;
+        frame.set(Some(at * PREVIEW_FRAMES / width as u32));
+    };
+
+    let style = move || {
+        frame.get().map(|i| {
+            let position = i as f64 / (PREVIEW_FRAMES - 1).max(1) as f64 * 100.;
+            format!(
+                "background-image:url('{sprite}');background-size:{}% auto;\
+                 background-position:{position}% center;background-repeat:no-repeat",
+                PREVIEW_FRAMES * 100
+            )
+        })
+    };
+
+    view! {
+        <div
+            class="absolute inset-1 rounded"
+            class:bg-black=move || frame.get().is_some()
+            style=style
+            on:mousemove=on_move
+            on:mouseleave=move |_| frame.set(None)
+        ></div>
+    }
+}
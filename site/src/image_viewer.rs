@@ -0,0 +1,188 @@
+use common::{
+    thumbnailable, GlobalState, GlobalStateStoreFields, Store, Unit, UnitKind, THUMBNAIL_PATH,
+};
+use leptos::{ev, prelude::*};
+use leptos_use::{
+    use_event_listener, use_interval_fn_with_options, use_window, UseIntervalFnOptions,
+};
+use web_sys::{MouseEvent, WheelEvent};
+
+use crate::files_box::{media_url, origin_with};
+
+const DISPLAY_SIZE: u32 = 2048;
+const SLIDESHOW_INTERVAL_MS: u64 = 4000;
+const MIN_ZOOM: f64 = 1.;
+const MAX_ZOOM: f64 = 8.;
+
+/// the original for what the server can not decode or would spoil, a large thumbnail otherwise
+fn display_src(unit: &Unit) -> String {
+    // re-encoded, a gif loses its animation
+    let animated = unit
+        .path
+        .extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| x.eq_ignore_ascii_case("gif"));
+    let original = animated || !thumbnailable(&unit.path);
+    if original {
+        origin_with(&format!(
+            "/download/{}",
+            unit.path.to_str().unwrap_or_default()
+        ))
+    } else {
+        format!(
+            "{}&size={DISPLAY_SIZE}",
+            media_url(THUMBNAIL_PATH, &unit.path)
+        )
+    }
+}
+
+#[component]
+pub fn ImageViewer(units: Memo<Option<Vec<Unit>>>) -> impl IntoView {
+    let store: Store<GlobalState> = use_context().unwrap();
+    let image_view = store.image_view();
+
+    let zoom = RwSignal::new(MIN_ZOOM);
+    let pan = RwSignal::new((0., 0.));
+    let dragging = RwSignal::new(None::<(i32, i32)>);
+
+    let reset = move || {
+        zoom.set(MIN_ZOOM);
+        pan.set((0., 0.));
+    };
+
+    let step = move |by: isize| {
+        let Some(current) = image_view.get_untracked() else {
+            return;
+        };
+        let images = units
+            .get_untracked()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|x| x.kind == UnitKind::Image)
+            .collect::<Vec<_>>();
+        let Some(at) = images.iter().position(|x| *x == current) else {
+            return;
+        };
+        let len = images.len() as isize;
+        let next = (at as isize + by).rem_euclid(len) as usize;
+        reset();
+        image_view.set(Some(images[next].clone()));
+    };
+
+    let slideshow = use_interval_fn_with_options(
+        move || step(1),
+        SLIDESHOW_INTERVAL_MS,
+        UseIntervalFnOptions::default().immediate(false),
+    );
+    let playing = slideshow.is_active;
+    let toggle_slideshow = {
+        let pause = slideshow.pause.clone();
+        let resume = slideshow.resume.clone();
+        move || {
+            if playing.get_untracked() {
+                pause();
+            } else {
+                resume();
+            }
+        }
+    };
+    let close = {
+        let pause = slideshow.pause.clone();
+        move || {
+            pause();
+            reset();
+            image_view.set(None);
+        }
+    };
+
+    let _ = use_event_listener(use_window(), ev::keydown, {
+        let toggle_slideshow = toggle_slideshow.clone();
+        let close = close.clone();
+        move |ev| {
+            if image_view.read_untracked().is_none() {
+                return;
+            }
+            match ev.key().as_str() {
+                "ArrowRight" => step(1),
+                "ArrowLeft" => step(-1),
+                "Escape" => close(),
+                " " => {
+                    ev.prevent_default();
+                    toggle_slideshow();
+                }
+                "+" | "=" => zoom.update(|x| *x = (*x * 1.25).min(MAX_ZOOM)),
+                "-" => zoom.update(|x| *x = (*x / 1.25).max(MIN_ZOOM)),
+                "0" => reset(),
+                _ => (),
+            }
+        }
+    });
+
+    let on_wheel = move |ev: WheelEvent| {
+        ev.prevent_default();
+        let factor = if ev.delta_y() < 0. { 1.15 } else { 1. / 1.15 };
+        zoom.update(|x| *x = (*x * factor).clamp(MIN_ZOOM, MAX_ZOOM));
+        if zoom.get_untracked() == MIN_ZOOM {
+            pan.set((0., 0.));
+        }
+    };
+    let on_down = move |ev: MouseEvent| {
+        ev.prevent_default();
+        dragging.set(Some((ev.client_x(), ev.client_y())));
+    };
+    let on_move = move |ev: MouseEvent| {
+        let Some((x, y)) = dragging.get_untracked() else {
+            return;
+        };
+        let (dx, dy) = (ev.client_x() - x, ev.client_y() - y);
+        let scale = zoom.get_untracked();
+        pan.update(|(px, py)| {
+            *px += dx as f64 / scale;
+            *py += dy as f64 / scale;
+        });
+        dragging.set(Some((ev.client_x(), ev.client_y())));
+    };
+
+    let transform = move || {
+        let (x, y) = pan.get();
+        format!("transform: scale({}) translate({x}px, {y}px)", zoom.get())
+    };
+
+    move || {
+        image_view.get().map(|unit| {
+            let toggle_slideshow = toggle_slideshow.clone();
+            let close = close.clone();
+            view! {
+                <div class="fixed inset-0 z-30 bg-black/90 flex flex-col select-none">
+                    <div class="flex items-center gap-4 p-2 text-white text-2xl">
+                        <span class="grow truncate">{unit.name()}</span>
+                        <button on:click=move |_| step(-1)>"‹"</button>
+                        <button on:click=move |_| toggle_slideshow()>
+                            {move || if playing.get() { "❚❚" } else { "▶" }}
+                        </button>
+                        <button on:click=move |_| step(1)>"›"</button>
+                        <button on:click=move |_| close()>"✕"</button>
+                    </div>
+                    <div
+                        class="grow overflow-hidden flex items-center justify-center"
+                        class:cursor-grab=move || dragging.get().is_none()
+                        class:cursor-grabbing=move || dragging.get().is_some()
+                        on:wheel=on_wheel
+                        on:mousedown=on_down
+                        on:mousemove=on_move
+                        on:mouseup=move |_| dragging.set(None)
+                        on:mouseleave=move |_| dragging.set(None)
+                        on:dblclick=move |_| reset()
+                    >
+                        <img
+                            class="max-w-full max-h-full object-contain"
+                            style=transform
+                            src=display_src(&unit)
+                            draggable="false"
+                        />
+                    </div>
+                </div>
+            }
+        })
+    }
+}
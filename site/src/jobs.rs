@@ -0,0 +1,66 @@
+use common::{GlobalState, GlobalStateStoreFields, JobInfo, JobState, Store, JOBS_PATH};
+use leptos::prelude::*;
+use std::time::Duration;
+
+use crate::files_box::origin_with;
+
+const POLL_TIME: Duration = Duration::from_secs(1);
+
+async fn jobs() -> Result<Vec<JobInfo>, String> {
+    reqwest::Client::new()
+        .get(origin_with(JOBS_PATH))
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<Vec<JobInfo>>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+/// the server's background work, polled while some of it runs
+#[component]
+pub fn Jobs() -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let list = LocalResource::new(jobs);
+    let running = StoredValue::new(0usize);
+
+    Effect::new(move || {
+        let _ = store.jobs_refetch_tick().read();
+        list.refetch();
+    });
+
+    Effect::new(move || {
+        let Some(Ok(jobs)) = list.get() else {
+            return;
+        };
+        let now = jobs.iter().filter(|x| x.state == JobState::Running).count();
+        // what finished jobs made is not listed yet
+        if now < running.get_value() {
+            store.units_refetch_tick().update(|x| *x = !*x);
+        }
+        running.set_value(now);
+        if now > 0 {
+            set_timeout(move || list.refetch(), POLL_TIME);
+        }
+    });
+
+    let jobs = move || match list.get() {
+        Some(Ok(jobs)) => jobs,
+        _ => Vec::new(),
+    };
+
+    view! {
+        <ul class="fixed bottom-5 left-5 grid gap-2 z-30">
+            <For each=jobs key=|job| (job.id, job.done, job.state.clone()) let:job>
+                <li class="bg-white border-2 border-lime-500 rounded-lg px-4 py-2 grid gap-1 max-w-sm">
+                    <span class="truncate">{job.name.clone()}</span>
+                    <progress max="100" value=job.percent()></progress>
+                    {match job.state {
+                        JobState::Failed(err) => Some(view! { <span class="text-sm text-red-600">{err}</span> }),
+                        _ => None,
+                    }}
+                </li>
+            </For>
+        </ul>
+    }
+}
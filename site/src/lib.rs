@@ -2,7 +2,10 @@ use std::path::PathBuf;
 
 use common::{GlobalState, GlobalStateStoreFields, SelectedState, SortUnits};
 use common::{Retype, Unit};
-use files_box::{ls, FilesBox};
+use events::Notices;
+use files_box::{link, ls, probe_units, settings, shares, FilesBox};
+use image_viewer::ImageViewer;
+use jobs::Jobs;
 use leptos::html::Ol;
 use leptos::{ev, prelude::*};
 use leptos_meta::*;
@@ -11,9 +14,18 @@ use leptos_use::{
     use_drop_zone_with_options, use_event_listener, use_window, UseDropZoneOptions,
     UseDropZoneReturn,
 };
+use link::LinkGate;
+use media_player::MediaPlayer;
+use music::MusicView;
 use nav_bar::NavBar;
 
+mod events;
 mod files_box;
+mod image_viewer;
+mod jobs;
+mod link;
+mod media_player;
+mod music;
 mod nav_bar;
 
 #[component]
@@ -37,6 +49,8 @@ pub fn App() -> impl IntoView {
             })
             .map(|mut xs| {
                 xs.sort_units();
+                // shares come first, like the roots they are
+                xs.sort_by_key(|x| store.with(|s| s.share_of(x).is_none()));
                 xs
             });
         if result.is_some() {
@@ -46,6 +60,9 @@ pub fn App() -> impl IntoView {
         }
     });
 
+    let probes = LocalResource::new(move || probe_units(units.get().unwrap_or_default()));
+    let probes = Signal::derive(move || probes.get().unwrap_or_default());
+
     provide_meta_context();
     provide_context(store);
 
@@ -54,6 +71,20 @@ pub fn App() -> impl IntoView {
         ls_result.refetch();
     });
 
+    let shares = LocalResource::new(shares);
+    Effect::new(move || match shares.get() {
+        Some(Ok(x)) => *store.shares().write() = x,
+        Some(Err(err)) => leptos::logging::error!("shares Error : {err}"),
+        None => (),
+    });
+
+    let settings = LocalResource::new(settings);
+    Effect::new(move || match settings.get() {
+        Some(Ok(x)) => *store.settings().write() = x,
+        Some(Err(err)) => leptos::logging::error!("settings Error : {err}"),
+        None => (),
+    });
+
     let _ = use_event_listener(use_window(), ev::popstate, move |_| {
         if let SelectedState::None = store.select().get().state {
             store.select().write().clear();
@@ -66,18 +97,39 @@ pub fn App() -> impl IntoView {
         files,
     } = use_drop_zone_with_options(drop_zone_el, UseDropZoneOptions::default());
 
+    // a share link shows the same site, rooted at what it points to
+    let link = link();
+    let shared = link.is_some();
+
     view! {
-        <Router>
+        <Router base=link.unwrap_or_default()>
             <NavBar files current_path/>
             <main>
                 <Routes fallback=|| "Page not found.">
                     <Route
                         path=StaticSegment("")
-                        view=move || view! { <FilesBox drop_zone_el is_over_drop_zone current_path units/> }
+                        view=move || {
+                            if store.music().get() {
+                                view! { <MusicView current_path /> }.into_any()
+                            } else {
+                                view! { <FilesBox drop_zone_el is_over_drop_zone current_path units probes/> }
+                                    .into_any()
+                            }
+                        }
                     />
                 </Routes>
             </main>
-            // <MediaPlayer />
+            <ImageViewer units />
+            <MediaPlayer units />
+            {if shared {
+                view! { <LinkGate /> }.into_any()
+            } else {
+                view! {
+                    <Notices />
+                    <Jobs />
+                }
+                .into_any()
+            }}
         </Router>
     }
 }
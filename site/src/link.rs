@@ -0,0 +1,95 @@
+use crate::files_box::origin_with;
+use common::{GlobalState, GlobalStateStoreFields, PublicLink, Store, LINK_INFO_PATH, UNLOCK_PATH};
+use leptos::prelude::*;
+use web_sys::KeyboardEvent;
+
+async fn link_info() -> Result<PublicLink, String> {
+    reqwest::Client::new()
+        .get(origin_with(LINK_INFO_PATH))
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<PublicLink>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+async fn unlock(password: String) -> Result<(), String> {
+    let res = reqwest::Client::new()
+        .post(origin_with(UNLOCK_PATH))
+        .json(&password)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .text()
+        .await
+        .map_err(|x| x.to_string())?;
+    // success sets a cookie and has no body, failures are the serialized server error
+    if res.is_empty() {
+        Ok(())
+    } else {
+        Err(res)
+    }
+}
+
+/// stands in front of a share link until its password is given, or tells that it is gone
+#[component]
+pub fn LinkGate() -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let info = LocalResource::new(link_info);
+    let password = RwSignal::new(String::new());
+    let action = Action::new_local(|x: &String| unlock(x.clone()));
+
+    Effect::new(move || {
+        if let Some(Ok(())) = action.value().get() {
+            info.refetch();
+            store.units_refetch_tick().update(|x| *x = !*x);
+        }
+    });
+
+    let enter = move |ev: KeyboardEvent| {
+        if ev.key() == "Enter" {
+            action.dispatch(password.get_untracked());
+        }
+    };
+    let wrong = move || matches!(action.value().get(), Some(Err(_)));
+
+    let card = move |body: AnyView| {
+        view! {
+            <div class="fixed inset-0 z-20 bg-white grid place-content-center">
+                <div class="border-2 border-lime-500 rounded-lg p-5 grid gap-3 text-xl">{body}</div>
+            </div>
+        }
+    };
+
+    move || match info.get() {
+        None | Some(Ok(PublicLink { locked: false, .. })) => None,
+        Some(Err(_)) => Some(card(
+            view! { <p>"this link expired or does not exist"</p> }.into_any(),
+        )),
+        Some(Ok(link)) => Some(card(
+            view! {
+                <h2 class="font-bold truncate">{link.name}</h2>
+                <input
+                    class="p-2 border-2 border-black"
+                    type="password"
+                    placeholder="password"
+                    bind:value=password
+                    on:keypress=enter
+                />
+                <button
+                    class="border-2 border-black rounded-lg p-2 hover:bg-black hover:text-white"
+                    on:click=move |_| {
+                        action.dispatch(password.get_untracked());
+                    }
+                >
+                    "open"
+                </button>
+                <Show when=wrong>
+                    <p class="text-red-600">"wrong password"</p>
+                </Show>
+            }
+            .into_any(),
+        )),
+    }
+}
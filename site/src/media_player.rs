@@ -1,37 +1,155 @@
-use common::Store;
-use leptos::{html::Div, prelude::*};
+use common::{Store, Subtitle, UnitKind, HLS_PATH, SUBTITLES_PATH, SUBTITLE_PATH};
+use leptos::{
+    either::Either,
+    ev,
+    html::{Div, Video},
+    prelude::*,
+};
 use leptos_use::{
-    UseDraggableOptions, UseDraggableReturn, core::Position, use_draggable_with_options,
+    core::Position, use_draggable_with_options, use_event_listener, use_window,
+    UseDraggableOptions, UseDraggableReturn,
+};
+use send_wrapper::SendWrapper;
+use web_sys::{
+    js_sys::{Function, Object, Reflect},
+    wasm_bindgen::{JsCast, JsValue},
+    Event, HtmlMediaElement,
 };
 
-use crate::Unit;
+use crate::{
+    files_box::{media_url, origin_with},
+    Unit,
+};
 
 use super::{GlobalState, GlobalStateStoreFields};
 
+const PLAYER_ID: &str = "media-player";
+const SEEK_STEP: f64 = 5.;
+/// positions this close to either end are not worth resuming from
+const RESUME_MARGIN: f64 = 5.;
+
+fn player() -> Option<HtmlMediaElement> {
+    document()
+        .get_element_by_id(PLAYER_ID)
+        .map(|x| x.unchecked_into::<HtmlMediaElement>())
+}
+
+fn position_key(unit: &Unit) -> String {
+    format!("webls-position:{}", unit.path.to_str().unwrap_or_default())
+}
+
+fn saved_position(unit: &Unit) -> Option<f64> {
+    window()
+        .local_storage()
+        .ok()
+        .flatten()?
+        .get_item(&position_key(unit))
+        .ok()
+        .flatten()?
+        .parse()
+        .ok()
+}
+
+fn save_position(unit: &Unit, position: Option<f64>) {
+    let Some(storage) = window().local_storage().ok().flatten() else {
+        return;
+    };
+    let key = position_key(unit);
+    let _ = match position {
+        Some(position) => storage.set_item(&key, &position.to_string()),
+        None => storage.remove_item(&key),
+    };
+}
+
 #[component]
-pub fn MediaPlayer() -> impl IntoView {
+pub fn MediaPlayer(units: Memo<Option<Vec<Unit>>>) -> impl IntoView {
     let store: Store<GlobalState> = use_context().unwrap();
     let media_play = store.media_play();
     let el = NodeRef::<Div>::new();
+    let handle = NodeRef::<Div>::new();
 
     let UseDraggableReturn { y, x, .. } = use_draggable_with_options(
         el,
         UseDraggableOptions::default()
+            .handle(Some(handle))
             .initial_value(Position { x: 0.0, y: 40.0 })
             .prevent_default(true),
     );
     let style = move || format!("left: {}px; top: {}px", x.get(), y.get().abs());
 
+    let playlist = Memo::new(move |_| {
+        // the music view queues an album, which wins for as long as one of its tracks plays
+        let queue = store.queue().get();
+        if media_play.with(|x| x.as_ref().is_some_and(|x| queue.contains(x))) {
+            return queue;
+        }
+        units
+            .get()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|x| matches!(x.kind, UnitKind::Video | UnitKind::Audio))
+            .collect::<Vec<_>>()
+    });
+
+    let step = move |by: isize| {
+        let Some(current) = media_play.get_untracked() else {
+            return;
+        };
+        let playlist = playlist.get_untracked();
+        let Some(at) = playlist.iter().position(|x| *x == current) else {
+            return;
+        };
+        let next = (at as isize + by).rem_euclid(playlist.len() as isize) as usize;
+        media_play.set(Some(playlist[next].clone()));
+    };
+
+    let _ = use_event_listener(use_window(), ev::keydown, move |ev| {
+        if media_play.read_untracked().is_none() || store.image_view().read_untracked().is_some() {
+            return;
+        }
+        let typing = ev
+            .target()
+            .and_then(|x| x.dyn_into::<web_sys::HtmlInputElement>().ok())
+            .is_some();
+        let Some(player) = player().filter(|_| !typing) else {
+            return;
+        };
+        match ev.key().as_str() {
+            " " | "k" => {
+                ev.prevent_default();
+                if player.paused() {
+                    let _ = player.play();
+                } else {
+                    let _ = player.pause();
+                }
+            }
+            "ArrowRight" => player.set_current_time(player.current_time() + SEEK_STEP),
+            "ArrowLeft" => player.set_current_time((player.current_time() - SEEK_STEP).max(0.)),
+            "m" => player.set_muted(!player.muted()),
+            "n" => step(1),
+            "p" => step(-1),
+            "Escape" => media_play.set(None),
+            _ => (),
+        }
+    });
+
     move || {
         media_play.get().map(|unit| {
             view! {
                 <div
                     node_ref=el
-                    class="fixed touch-none bg-white rounded-lg text-2xl px-4 py-2 border border-gray-400/30 shadow-xs hover:shadow-lg select-none cursor-move z-24"
+                    class="fixed touch-none bg-white rounded-lg text-2xl px-4 py-2 border border-gray-400/30 shadow-xs hover:shadow-lg select-none z-24"
                     style=style
                 >
-                    <Bar name=unit.name() />
-                    <Player unit />
+                    <div node_ref=handle class="cursor-move">
+                        <Bar name=unit.name() />
+                    </div>
+                    <Player unit=unit.clone() on_ended=move || step(1) />
+                    <div class="flex justify-center gap-6 my-2">
+                        <button on:click=move |_| step(-1)>"⏮"</button>
+                        <button on:click=move |_| step(1)>"⏭"</button>
+                    </div>
+                    <Playlist playlist current=unit />
                 </div>
             }
         })
@@ -89,19 +207,240 @@ fn CloseIcon() -> impl IntoView {
     }
 }
 
+/// whether the browser claims it can decode the container, codecs are only known by trying
+fn playable(unit: &Unit) -> bool {
+    let mime = match unit.path.extension().and_then(|x| x.to_str()) {
+        Some("mp4" | "m4v") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("ogv" | "ogg") => "video/ogg",
+        Some("mov" | "qt") => "video/quicktime",
+        Some("mkv") => "video/x-matroska",
+        _ => return false,
+    };
+    document()
+        .create_element("video")
+        .map(|x| {
+            !x.unchecked_into::<HtmlMediaElement>()
+                .can_play_type(mime)
+                .is_empty()
+        })
+        .unwrap_or_default()
+}
+
+/// asks the server for a transcode and returns where its playlist ended up
+async fn hls_playlist(unit: Unit) -> Result<String, String> {
+    let res = reqwest::get(media_url(HLS_PATH, &unit.path))
+        .await
+        .map_err(|x| x.to_string())?;
+    if !res.status().is_success() {
+        return Err(res.text().await.map_err(|x| x.to_string())?);
+    }
+    Ok(res.url().to_string())
+}
+
+async fn subtitles(unit: Unit) -> Result<Vec<Subtitle>, String> {
+    reqwest::get(media_url(SUBTITLES_PATH, &unit.path))
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<Vec<Subtitle>>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+fn subtitle_url(subtitle: &Subtitle) -> String {
+    let url = media_url(SUBTITLE_PATH, &subtitle.path);
+    match subtitle.stream {
+        Some(stream) => format!("{url}&stream={stream}"),
+        None => url,
+    }
+}
+
+fn js_method(target: &JsValue, name: &str) -> Option<Function> {
+    Reflect::get(target, &name.into()).ok()?.dyn_into().ok()
+}
+
+/// hands `el` over to the vendored video.js, which plays hls where the browser can not
+fn videojs(el: &HtmlMediaElement, playlist: &str) -> Option<JsValue> {
+    let options = Object::new();
+    Reflect::set(&options, &"controls".into(), &true.into()).ok()?;
+    Reflect::set(&options, &"autoplay".into(), &true.into()).ok()?;
+    let player = js_method(&window(), "videojs")?
+        .call2(&JsValue::NULL, el, &options)
+        .ok()?;
+
+    let source = Object::new();
+    Reflect::set(&source, &"src".into(), &playlist.into()).ok()?;
+    Reflect::set(&source, &"type".into(), &"application/x-mpegURL".into()).ok()?;
+    js_method(&player, "src")?.call1(&player, &source).ok()?;
+    Some(player)
+}
+
 #[component]
-fn Player(unit: Unit) -> impl IntoView {
-    let src = format!("/download/{}", unit.path.to_str().unwrap());
-    view! {
+fn Player<OnEnded>(unit: Unit, on_ended: OnEnded) -> impl IntoView
+where
+    OnEnded: Fn() + Send + Sync + 'static,
+{
+    let src = origin_with(&format!(
+        "/download/{}",
+        unit.path.to_str().unwrap_or_default()
+    ));
+    let target = |ev: Event| ev.target().unwrap().unchecked_into::<HtmlMediaElement>();
+
+    let resume = {
+        let unit = unit.clone();
+        move |ev: Event| {
+            let player = target(ev);
+            if let Some(position) = saved_position(&unit)
+                .filter(|x| *x > RESUME_MARGIN && *x < player.duration() - RESUME_MARGIN)
+            {
+                player.set_current_time(position);
+            }
+        }
+    };
+    let remember = {
+        let unit = unit.clone();
+        move |ev: Event| {
+            let position = target(ev).current_time().floor();
+            if position > RESUME_MARGIN && saved_position(&unit) != Some(position) {
+                save_position(&unit, Some(position));
+            }
+        }
+    };
+    let ended = {
+        let unit = unit.clone();
+        move |_: Event| {
+            save_position(&unit, None);
+            on_ended();
+        }
+    };
+
+    if unit.kind == UnitKind::Audio {
+        return Either::Left(view! {
+            <audio
+                id=PLAYER_ID
+                class="w-96"
+                src=src
+                autoplay
+                controls
+                on:loadedmetadata=resume
+                on:timeupdate=remember
+                on:ended=ended
+            />
+        });
+    }
+
+    let hls = RwSignal::new(!playable(&unit));
+    let playlist = LocalResource::new({
+        let unit = unit.clone();
+        move || {
+            let unit = unit.clone();
+            async move {
+                if hls.get() {
+                    Some(hls_playlist(unit).await)
+                } else {
+                    None
+                }
+            }
+        }
+    });
+    let tracks = LocalResource::new({
+        let unit = unit.clone();
+        move || subtitles(unit.clone())
+    });
+    let tracks = move || {
+        tracks
+            .get()
+            .and_then(Result::ok)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|x| {
+                view! {
+                    <track
+                        kind="subtitles"
+                        src=subtitle_url(&x)
+                        srclang=x.language.clone()
+                        label=x.label.clone()
+                    />
+                }
+            })
+            .collect_view()
+    };
+    let video = NodeRef::<Video>::new();
+    let videojs_player = StoredValue::new(None::<SendWrapper<JsValue>>);
+
+    Effect::new(move || {
+        let (Some(el), Some(Ok(url))) = (video.get(), playlist.get().flatten()) else {
+            return;
+        };
+        if videojs_player.with_value(Option::is_none) {
+            let player = videojs(&el, &url).map(SendWrapper::new);
+            videojs_player.set_value(player);
+        }
+    });
+    on_cleanup(move || {
+        if let Some(player) = videojs_player.get_value() {
+            if let Some(dispose) = js_method(&player, "dispose") {
+                let _ = dispose.call0(&player);
+            }
+        }
+    });
+
+    let on_error = move |_| {
+        if !hls.get_untracked() {
+            hls.set(true);
+        }
+    };
+    let transcode_error =
+        move || {
+            playlist.get().flatten().and_then(Result::err).map(
+                |err| view! { <p class="text-red-600 text-lg">"can not transcode : " {err}</p> },
+            )
+        };
+
+    Either::Right(view! {
         <video
-           id="my-player"
-           preload="auto"
-           class="rounded-lg cursor-default video-js"
-           autoplay
-           controls
+            node_ref=video
+            id=PLAYER_ID
+            class="rounded-lg cursor-default max-w-[80vw] max-h-[60vh]"
+            src=move || (!hls.get()).then(|| src.clone())
+            preload="auto"
+            autoplay
+            controls
+            on:loadedmetadata=resume
+            on:timeupdate=remember
+            on:ended=ended
+            on:error=on_error
         >
-            <source src=src type="video/mp4"/>
+            {tracks}
             "Your browser does not support the video tag."
         </video>
+        {transcode_error}
+    })
+}
+
+#[component]
+fn Playlist(playlist: Memo<Vec<Unit>>, current: Unit) -> impl IntoView {
+    let store: Store<GlobalState> = use_context().unwrap();
+
+    view! {
+        <ol class="max-h-48 overflow-y-auto text-lg">
+            <For each=move || playlist.get() key=|x| x.path.clone() let:unit>
+                {
+                    let playing = unit == current;
+                    let name = unit.name();
+                    view! {
+                        <li>
+                            <button
+                                class="w-full text-left truncate hover:bg-black hover:text-white px-2"
+                                class:font-bold=playing
+                                on:click=move |_| store.media_play().set(Some(unit.clone()))
+                            >
+                                {name}
+                            </button>
+                        </li>
+                    }
+                }
+            </For>
+        </ol>
     }
 }
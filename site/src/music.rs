@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use common::{
+    duration_text, GlobalState, GlobalStateStoreFields, Store, Track, COVER_PATH, MUSIC_PATH,
+};
+use leptos::prelude::*;
+use web_sys::wasm_bindgen::JsCast;
+
+use crate::files_box::{media_url, origin_with};
+
+async fn music(base: PathBuf) -> Result<Vec<Track>, String> {
+    reqwest::Client::new()
+        .post(origin_with(MUSIC_PATH))
+        .json(&base)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<Vec<Track>>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+struct Album {
+    name: String,
+    tracks: Vec<Track>,
+}
+
+struct Artist {
+    name: String,
+    albums: Vec<Album>,
+}
+
+/// the server sends tracks ordered by artist and album, so groups are consecutive
+fn group(tracks: Vec<Track>) -> Vec<Artist> {
+    let mut artists: Vec<Artist> = Vec::new();
+    for track in tracks {
+        let artist = match artists.last_mut() {
+            Some(x) if x.name == track.artist() => x,
+            _ => {
+                artists.push(Artist {
+                    name: track.artist().to_string(),
+                    albums: Vec::new(),
+                });
+                artists.last_mut().unwrap()
+            }
+        };
+        match artist.albums.last_mut() {
+            Some(x) if x.name == track.album() => x.tracks.push(track),
+            _ => artist.albums.push(Album {
+                name: track.album().to_string(),
+                tracks: vec![track],
+            }),
+        }
+    }
+    artists
+}
+
+/// every audio file under the current directory, by artist and album
+#[component]
+pub fn MusicView(current_path: RwSignal<PathBuf>) -> impl IntoView {
+    let tracks = LocalResource::new(move || music(current_path.get()));
+
+    let artists = move || {
+        tracks.get().map(|x| match x {
+            Ok(tracks) if tracks.is_empty() => {
+                view! { <p>"no music in this directory"</p> }.into_any()
+            }
+            Ok(tracks) => group(tracks)
+                .into_iter()
+                .map(|artist| view! { <ArtistView artist /> })
+                .collect_view()
+                .into_any(),
+            Err(err) => view! { <p>{err}</p> }.into_any(),
+        })
+    };
+
+    view! {
+        <div class="w-full min-h-80 m-5 p-5 border-2 border-lime-500 rounded-lg">
+            <Suspense fallback=|| view! { <img src="load.gif" width=65 /> }>{artists}</Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn ArtistView(artist: Artist) -> impl IntoView {
+    view! {
+        <section class="mb-8">
+            <h2 class="text-3xl font-bold mb-3">{artist.name}</h2>
+            {artist.albums.into_iter().map(|album| view! { <AlbumView album /> }).collect_view()}
+        </section>
+    }
+}
+
+#[component]
+fn AlbumView(album: Album) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let queue = album.tracks.iter().map(Track::unit).collect::<Vec<_>>();
+    let cover = media_url(COVER_PATH, &album.tracks[0].path);
+
+    let tracks = album
+        .tracks
+        .into_iter()
+        .map(|track| {
+            let queue = queue.clone();
+            let unit = track.unit();
+            let number = track.tags.track.map(|x| format!("{x}."));
+            let duration = track.duration.map(duration_text);
+            let play = move |_| {
+                *store.queue().write() = queue.clone();
+                *store.media_play().write() = Some(unit.clone());
+            };
+            view! {
+                <li>
+                    <button
+                        class="w-full grid grid-cols-[3rem_1fr_auto] text-left hover:bg-black hover:text-white px-2"
+                        on:dblclick=play
+                    >
+                        <span class="text-gray-500">{number}</span>
+                        <span class="truncate">{track.title()}</span>
+                        <span class="text-gray-500">{duration}</span>
+                    </button>
+                </li>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <div class="flex gap-5 mb-5">
+            <img
+                class="object-contain aspect-square rounded"
+                src=cover
+                width=160
+                loading="lazy"
+                // albums without any art fall back to the audio icon
+                on:error=|ev| {
+                    let img = ev.target().unwrap().unchecked_into::<web_sys::Element>();
+                    let _ = img.set_attribute("src", "public/audio.png");
+                }
+            />
+            <div class="grow">
+                <h3 class="text-2xl mb-2">{album.name}</h3>
+                <ol class="text-xl">{tracks}</ol>
+            </div>
+        </div>
+    }
+}
@@ -1,30 +1,42 @@
 use std::path::PathBuf;
 
+use crate::files_box::{archive_url, link};
+use archive::Archive;
 use atoms::{ActiveIcon, Icon};
-use common::{GlobalState, GlobalStateStoreFields, SelectedState, Store};
+use common::{ArchiveFormat, GlobalState, GlobalStateStoreFields, SelectedState, Store, UnitKind};
+use convert::Convert;
+use details::Details;
+use extract::Extract;
 use info::Info;
 use leptos::{either::either, prelude::*};
 use leptos_router::{hooks::use_navigate, NavigateOptions};
+use links::Links;
 use mp4::ToMp4;
 use paste::Paste;
 use rm::Remove;
 use send_wrapper::SendWrapper;
 use upload::Upload;
+use web_sys::{wasm_bindgen::JsCast, HtmlAnchorElement};
 
+mod archive;
+mod convert;
+mod details;
+mod extract;
 mod info;
+mod links;
 mod mp4;
 mod paste;
 mod rm;
 pub mod upload;
 
-//TODO : add button to navbar to refresh mounted disks
-
 #[component]
 pub fn NavBar(
     files: Signal<Vec<SendWrapper<web_sys::File>>>,
     current_path: RwSignal<PathBuf>,
 ) -> impl IntoView {
     let store: Store<GlobalState> = use_context().unwrap();
+    // whoever holds a share link only browses and downloads
+    let shared = link().is_some();
     let more = RwSignal::new(true);
     let hidden = move || {
         if more.get() {
@@ -49,17 +61,27 @@ pub fn NavBar(
             <div class="grid grid-cols-2 place-content-center" style=hidden>
                 <Home current_path/>
                 <Clear />
+                {(!shared).then(|| view! {
+                    <Gallery />
+                    <Music />
+                })}
                 <Download />
-                {move || {
-                    either!(
-                        store.password().get(),
-                            true => view! {
-                                <AdminRequired files current_path/>
-                            },
-                            false => view! {<Admin/>},
-                    )
-                }}
-                <Info/>
+                {(!shared).then(|| view! {
+                    <Details />
+                    {move || {
+                        // a read only server has nothing to administer
+                        let read_only = store.settings().read().read_only;
+                        either!(
+                            (read_only, store.password().get()),
+                                (true, _) => (),
+                                (false, true) => view! {
+                                    <AdminRequired files current_path/>
+                                },
+                                (false, false) => view! {<Admin/>},
+                        )
+                    }}
+                    <Info/>
+                })}
             </div>
         </nav>
     }
@@ -93,6 +115,10 @@ pub fn AdminRequired(
         <Mkdir />
         <Paste current_path/>
         <ToMp4  />
+        <Convert />
+        <Extract current_path/>
+        <Archive current_path/>
+        <Links />
     }
 }
 
@@ -162,18 +188,48 @@ fn Clear() -> impl IntoView {
     view! { <Tool name="clear" active onclick /> }
 }
 
+#[component]
+fn Gallery() -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let onclick = move || {
+        store.gallery().update(|x| *x = !*x);
+    };
+
+    view! { <Tool name="gallery" active=|| true onclick /> }
+}
+
+#[component]
+fn Music() -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let onclick = move || {
+        store.music().update(|x| *x = !*x);
+    };
+
+    view! { <Tool name="music" active=|| true onclick /> }
+}
+
 #[component]
 fn Download() -> impl IntoView {
     let store: Store<GlobalState> = use_context().unwrap();
     let onclick = move || {
-        store.select().get_untracked().download_selected();
+        let select = store.select().get_untracked();
+        match select.units.as_slice() {
+            [unit] if unit.kind != UnitKind::Dirctory => select.download_selected(),
+            // one archive instead of an anchor per file, which browsers throttle
+            _ => {
+                let anchor = document()
+                    .create_element("a")
+                    .unwrap()
+                    .unchecked_into::<HtmlAnchorElement>();
+                anchor.set_href(&archive_url(ArchiveFormat::Zip, &select.as_paths()));
+                anchor.set_download("");
+                anchor.click();
+            }
+        }
         store.select().write().clear();
     };
 
-    let active = move || {
-        let select = store.select().read();
-        !select.is_clear() && !select.has_dirs()
-    };
+    let active = move || !store.select().read().is_clear();
 
     view! { <Tool name="download" active onclick /> }
 }
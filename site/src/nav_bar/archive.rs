@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+use crate::files_box::origin_with;
+use crate::nav_bar::Tool;
+use common::{
+    ArchiveFormat, GlobalState, GlobalStateStoreFields, NewArchive, OutputNaming, Store,
+    ARCHIVE_PATH,
+};
+use leptos::{ev, html::Div, prelude::*};
+use leptos_use::{on_click_outside, use_event_listener, use_window};
+use web_sys::{wasm_bindgen::JsCast, Event, HtmlSelectElement};
+
+async fn archive(new: NewArchive) -> Result<u64, String> {
+    let res = reqwest::Client::new()
+        .post(origin_with(ARCHIVE_PATH))
+        .json(&new)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .text()
+        .await
+        .map_err(|x| x.to_string())?;
+    // the job id, or the serialized server error
+    res.parse().map_err(|_| res)
+}
+
+#[component]
+pub fn Archive(current_path: RwSignal<PathBuf>) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let display = RwSignal::new(false);
+    let action = Action::new_local(|input: &NewArchive| archive(input.clone()));
+
+    let onclick = move || {
+        display.set(true);
+    };
+
+    let active = move || !store.select().read().is_clear();
+
+    Effect::new(move || match action.value().get() {
+        Some(Ok(_)) => store.jobs_refetch_tick().update(|x| *x = !*x),
+        Some(Err(err)) => {
+            let _ = window().alert_with_message(&format!("no archive made : {err}"));
+        }
+        None => (),
+    });
+
+    view! {
+        <Tool active name="archive" onclick />
+        <Show when=move || display.get()>
+            <ArchiveCard display action current_path />
+        </Show>
+    }
+}
+
+#[component]
+fn ArchiveCard(
+    display: RwSignal<bool>,
+    action: Action<NewArchive, Result<u64, String>>,
+    current_path: RwSignal<PathBuf>,
+) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    // named after the one selected unit, or the directory holding the selection
+    let name = match store.select().read_untracked().units.as_slice() {
+        [unit] => unit.path.file_stem().map(|x| x.to_owned()),
+        _ => current_path
+            .get_untracked()
+            .file_name()
+            .map(|x| x.to_owned()),
+    }
+    .and_then(|x| x.into_string().ok())
+    .unwrap_or_else(|| String::from("archive"));
+    let name = RwSignal::new(name);
+    let format = RwSignal::new(ArchiveFormat::default());
+    let naming = RwSignal::new(OutputNaming::default());
+
+    let _ = use_event_listener(use_window(), ev::keydown, move |ev| {
+        if ev.key() == "Escape" {
+            display.set(false);
+        }
+    });
+
+    let target = NodeRef::<Div>::new();
+    let _ = on_click_outside(target, move |_| {
+        display.set(false);
+    });
+
+    let selected = |ev: Event| {
+        ev.target()
+            .unwrap()
+            .unchecked_into::<HtmlSelectElement>()
+            .selected_index()
+            .max(0) as usize
+    };
+
+    let submit = move |_| {
+        action.dispatch_local(NewArchive {
+            targets: store.select().read_untracked().as_paths(),
+            to: current_path.get_untracked(),
+            name: name.get_untracked().trim().to_string(),
+            format: format.get_untracked(),
+            naming: naming.get_untracked(),
+        });
+        store.select().write().clear();
+        display.set(false);
+    };
+
+    // streamed tars are not worth keeping, they are as big as what they hold
+    let formats = [ArchiveFormat::Zip, ArchiveFormat::TarZst];
+
+    view! {
+        <div
+            class="fixed top-1/2 left-1/2 transform -translate-x-1/2 -translate-y-1/2 bg-white border-2 border-lime-500 rounded-lg p-5 grid grid-cols-2 gap-3 text-xl"
+            node_ref=target
+        >
+            <label>"name"</label>
+            <input class="border-2 border-black" bind:value=name />
+            <label>"format"</label>
+            <select on:change=move |ev| format.set(formats[selected(ev)])>
+                {formats
+                    .map(|x| view! { <option selected=x == format.get_untracked()>{x.extension()}</option> })}
+            </select>
+            <label>"if the name is taken"</label>
+            <select on:change=move |ev| naming.set(OutputNaming::ALL[selected(ev)])>
+                {OutputNaming::ALL
+                    .map(|x| view! { <option selected=x == naming.get_untracked()>{x.to_string()}</option> })}
+            </select>
+            <button class="col-span-2 border-2 border-black rounded-lg p-2 hover:bg-black hover:text-white" on:click=submit>
+                "archive"
+            </button>
+        </div>
+    }
+}
@@ -0,0 +1,138 @@
+use crate::files_box::origin_with;
+use crate::nav_bar::LoadableTool;
+use common::{
+    Conversion, ConvertProfile, GlobalState, GlobalStateStoreFields, OutputNaming, Store, UnitKind,
+    CONVERT_PATH,
+};
+use leptos::{ev, html::Div, prelude::*};
+use leptos_use::{on_click_outside, use_event_listener, use_window};
+use web_sys::{wasm_bindgen::JsCast, Event, HtmlInputElement, HtmlSelectElement};
+
+async fn convert(conversion: Conversion) -> Result<(), String> {
+    let res = reqwest::Client::new()
+        .post(origin_with(CONVERT_PATH))
+        .json(&conversion)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .text()
+        .await
+        .map_err(|x| x.to_string())?;
+    // success has no body, failures are the serialized server error
+    if res.is_empty() {
+        Ok(())
+    } else {
+        Err(res)
+    }
+}
+
+#[component]
+pub fn Convert() -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let display = RwSignal::new(false);
+    let action = Action::new_local(|input: &Conversion| convert(input.clone()));
+
+    let onclick = move || {
+        display.set(true);
+    };
+
+    let active = move || {
+        let select = store.select().read();
+        !select.is_clear()
+            && select
+                .units
+                .iter()
+                .all(|x| matches!(x.kind, UnitKind::Video | UnitKind::Audio))
+    };
+
+    let finished = move || !action.pending().get();
+
+    Effect::new(move || {
+        if finished() {
+            store.units_refetch_tick().update(|x| *x = !*x);
+        }
+    });
+
+    Effect::new(move || {
+        if let Some(Err(err)) = action.value().get() {
+            let _ = window().alert_with_message(&format!("conversion failed : {err}"));
+        }
+    });
+
+    view! {
+        <LoadableTool active name="convert" onclick finished />
+        <Show when=move || display.get()>
+            <ConvertCard display action />
+        </Show>
+    }
+}
+
+#[component]
+fn ConvertCard(
+    display: RwSignal<bool>,
+    action: Action<Conversion, Result<(), String>>,
+) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let profile = RwSignal::new(ConvertProfile::default());
+    let naming = RwSignal::new(OutputNaming::default());
+    let keep_source = RwSignal::new(true);
+
+    let _ = use_event_listener(use_window(), ev::keydown, move |ev| {
+        if ev.key() == "Escape" {
+            display.set(false);
+        }
+    });
+
+    let target = NodeRef::<Div>::new();
+    let _ = on_click_outside(target, move |_| {
+        display.set(false);
+    });
+
+    let selected = |ev: Event| {
+        ev.target()
+            .unwrap()
+            .unchecked_into::<HtmlSelectElement>()
+            .selected_index()
+            .max(0) as usize
+    };
+
+    let submit = move |_| {
+        action.dispatch_local(Conversion {
+            targets: store.select().read_untracked().as_paths(),
+            profile: profile.get_untracked(),
+            naming: naming.get_untracked(),
+            keep_source: keep_source.get_untracked(),
+        });
+        store.select().write().clear();
+        display.set(false);
+    };
+
+    view! {
+        <div
+            class="fixed top-1/2 left-1/2 transform -translate-x-1/2 -translate-y-1/2 bg-white border-2 border-lime-500 rounded-lg p-5 grid grid-cols-2 gap-3 text-xl"
+            node_ref=target
+        >
+            <label>"profile"</label>
+            <select on:change=move |ev| profile.set(ConvertProfile::ALL[selected(ev)])>
+                {ConvertProfile::ALL
+                    .map(|x| view! { <option selected=x == profile.get_untracked()>{x.to_string()}</option> })}
+            </select>
+            <label>"if the name is taken"</label>
+            <select on:change=move |ev| naming.set(OutputNaming::ALL[selected(ev)])>
+                {OutputNaming::ALL
+                    .map(|x| view! { <option selected=x == naming.get_untracked()>{x.to_string()}</option> })}
+            </select>
+            <label>"keep the source"</label>
+            <input
+                type="checkbox"
+                prop:checked=keep_source
+                on:change=move |ev: Event| {
+                    keep_source.set(ev.target().unwrap().unchecked_into::<HtmlInputElement>().checked())
+                }
+            />
+            <button class="col-span-2 border-2 border-black rounded-lg p-2 hover:bg-black hover:text-white" on:click=submit>
+                "convert"
+            </button>
+        </div>
+    }
+}
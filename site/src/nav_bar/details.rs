@@ -0,0 +1,238 @@
+use std::path::PathBuf;
+
+use crate::{
+    files_box::{origin_with, probe},
+    nav_bar::Tool,
+};
+use common::{
+    Checksums, FileChecksum, GlobalState, GlobalStateStoreFields, HashAlgorithm, MediaInfo,
+    MediaStream, Store, StreamKind, Unit, UnitKind, CHECKSUMS_PATH, CHECKSUM_PATH,
+};
+use leptos::{ev, html::Div, prelude::*};
+use leptos_use::{on_click_outside, use_event_listener, use_window};
+
+async fn known(path: PathBuf) -> Result<Vec<FileChecksum>, String> {
+    reqwest::Client::new()
+        .post(origin_with(CHECKSUMS_PATH))
+        .json(&[path])
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<Vec<FileChecksum>>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+async fn checksum(checksums: Checksums) -> Result<u64, String> {
+    let res = reqwest::Client::new()
+        .post(origin_with(CHECKSUM_PATH))
+        .json(&checksums)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .text()
+        .await
+        .map_err(|x| x.to_string())?;
+    // the job id, or the serialized server error
+    res.parse().map_err(|_| res)
+}
+
+#[component]
+pub fn Details() -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let display = RwSignal::new(None::<Unit>);
+
+    let selected = move || {
+        let select = store.select().read();
+        match select.units.as_slice() {
+            [unit] if unit.kind != UnitKind::Dirctory => Some(unit.clone()),
+            _ => None,
+        }
+    };
+
+    let onclick = move || {
+        display.set(selected());
+    };
+
+    let active = move || selected().is_some();
+    view! {
+        <Tool name="details" active onclick />
+        {move || display.get().map(|unit| view! { <DetailsCard unit display /> })}
+    }
+}
+
+#[component]
+fn DetailsCard(unit: Unit, display: RwSignal<Option<Unit>>) -> impl IntoView {
+    let media = matches!(unit.kind, UnitKind::Video | UnitKind::Audio);
+    let info = LocalResource::new({
+        let path = unit.path.clone();
+        move || {
+            let path = path.clone();
+            async move {
+                if media {
+                    probe(vec![path]).await
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+        }
+    });
+
+    let _ = use_event_listener(use_window(), ev::keydown, move |ev| {
+        if ev.key() == "Escape" {
+            display.set(None);
+        }
+    });
+
+    let target = NodeRef::<Div>::new();
+    let _ = on_click_outside(target, move |_| {
+        display.set(None);
+    });
+
+    let body = move || {
+        let info = info.get()?;
+        Some(match info.map(|mut xs| xs.pop().flatten()) {
+            Ok(Some(info)) => view! { <MediaInfoView info /> }.into_any(),
+            Ok(None) if media => view! { <p>"ffprobe can not read this file"</p> }.into_any(),
+            Ok(None) => ().into_any(),
+            Err(err) => view! { <p>{err}</p> }.into_any(),
+        })
+    };
+
+    view! {
+        <div
+            class="fixed top-1/2 left-1/2 transform -translate-x-1/2 -translate-y-1/2 bg-white border-2 border-lime-500 rounded-lg p-5 text-xl max-w-2xl"
+            node_ref=target
+        >
+            <h2 class="font-bold truncate mb-3">{unit.name()}</h2>
+            <Suspense fallback=|| view! { <img src="load.gif" width=65 /> }>{body}</Suspense>
+            <ChecksumsView path=unit.path.clone() />
+        </div>
+    }
+}
+
+/// the checksums known of the file, and the ones that can be computed in a job
+#[component]
+fn ChecksumsView(path: PathBuf) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let list = LocalResource::new({
+        let path = path.clone();
+        move || known(path.clone())
+    });
+    let compute = Action::new_local(move |algorithm: &HashAlgorithm| {
+        checksum(Checksums {
+            targets: vec![path.clone()],
+            algorithm: *algorithm,
+        })
+    });
+
+    // the jobs panel refetches the units when jobs end
+    Effect::new(move || {
+        let _ = store.units_refetch_tick().read();
+        list.refetch();
+    });
+
+    Effect::new(move || match compute.value().get() {
+        Some(Ok(_)) => store.jobs_refetch_tick().update(|x| *x = !*x),
+        Some(Err(err)) => {
+            let _ = window().alert_with_message(&format!("checksum failed : {err}"));
+        }
+        None => (),
+    });
+
+    let rows = move || {
+        let known = match list.get() {
+            Some(Ok(known)) => known,
+            Some(Err(err)) => return view! { <p>{err}</p> }.into_any(),
+            None => Vec::new(),
+        };
+        HashAlgorithm::ALL
+            .map(|algorithm| {
+                let hex = known
+                    .iter()
+                    .find(|x| x.algorithm == algorithm)
+                    .map(|x| x.hex.clone());
+                let value = match hex {
+                    Some(hex) => view! {
+                        <code class="text-sm break-all">{hex.clone()}</code>
+                        <button
+                            class="border-2 border-black rounded-lg px-2 hover:bg-black hover:text-white"
+                            on:click=move |_| {
+                                // the promise is dropped, copying still happens
+                                let _ = window().navigator().clipboard().write_text(&hex);
+                            }
+                        >
+                            "copy"
+                        </button>
+                    }
+                    .into_any(),
+                    None => view! {
+                        <span class="text-gray-500">"-"</span>
+                        <button
+                            class="border-2 border-black rounded-lg px-2 hover:bg-black hover:text-white"
+                            on:click=move |_| {
+                                compute.dispatch_local(algorithm);
+                            }
+                        >
+                            "compute"
+                        </button>
+                    }
+                    .into_any(),
+                };
+                view! {
+                    <dt>{algorithm.to_string()}</dt>
+                    {value}
+                }
+            })
+            .collect_view()
+            .into_any()
+    };
+
+    view! { <dl class="grid grid-cols-[auto_1fr_auto] gap-x-3 gap-y-1 items-center mt-3">{rows}</dl> }
+}
+
+#[component]
+fn MediaInfoView(info: MediaInfo) -> impl IntoView {
+    let bit_rate = info.bit_rate.map(|x| format!("{} kb/s", x / 1000));
+    let hint = if info.browser_friendly() {
+        "plays in the browser as is"
+    } else {
+        "needs conversion to play in every browser"
+    };
+    view! {
+        <dl class="grid grid-cols-2 gap-x-5">
+            <dt>"container"</dt>
+            <dd>{info.format.clone()}</dd>
+            <dt>"duration"</dt>
+            <dd>{info.duration_text().unwrap_or_else(|| "-".into())}</dd>
+            <dt>"bit rate"</dt>
+            <dd>{bit_rate.unwrap_or_else(|| "-".into())}</dd>
+        </dl>
+        <ul class="my-3">
+            {info.streams.iter().map(stream_line).map(|x| view! { <li>{x}</li> }).collect_view()}
+        </ul>
+        <p class="text-gray-500">{hint}</p>
+    }
+}
+
+fn stream_line(stream: &MediaStream) -> String {
+    let kind = match stream.kind {
+        StreamKind::Video => "video",
+        StreamKind::Audio => "audio",
+        StreamKind::Subtitle => "subtitle",
+        StreamKind::Other => "other",
+    };
+    let mut parts = vec![format!("{kind} : {}", stream.codec)];
+    if let (Some(w), Some(h)) = (stream.width, stream.height) {
+        parts.push(format!("{w}x{h}"));
+    }
+    if let Some(x) = stream.channels {
+        parts.push(format!("{x} ch"));
+    }
+    if let Some(x) = stream.sample_rate {
+        parts.push(format!("{x} Hz"));
+    }
+    parts.extend(stream.language.clone());
+    parts.extend(stream.title.clone());
+    parts.join(", ")
+}
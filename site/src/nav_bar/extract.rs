@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+
+use crate::files_box::origin_with;
+use crate::nav_bar::Tool;
+use common::{
+    ArchiveKind, Extraction, GlobalState, GlobalStateStoreFields, OutputNaming, Store, EXTRACT_PATH,
+};
+use leptos::{ev, html::Div, prelude::*};
+use leptos_use::{on_click_outside, use_event_listener, use_window};
+use web_sys::{wasm_bindgen::JsCast, Event, HtmlSelectElement};
+
+async fn extract(extraction: Extraction) -> Result<u64, String> {
+    let res = reqwest::Client::new()
+        .post(origin_with(EXTRACT_PATH))
+        .json(&extraction)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .text()
+        .await
+        .map_err(|x| x.to_string())?;
+    // the job id, or the serialized server error
+    res.parse().map_err(|_| res)
+}
+
+#[component]
+pub fn Extract(current_path: RwSignal<PathBuf>) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let display = RwSignal::new(false);
+    let action = Action::new_local(|input: &Extraction| extract(input.clone()));
+
+    let onclick = move || {
+        display.set(true);
+    };
+
+    let active = move || {
+        let select = store.select().read();
+        !select.is_clear()
+            && select
+                .units
+                .iter()
+                .all(|x| ArchiveKind::of(&x.path).is_some())
+    };
+
+    Effect::new(move || match action.value().get() {
+        Some(Ok(_)) => store.jobs_refetch_tick().update(|x| *x = !*x),
+        Some(Err(err)) => {
+            let _ = window().alert_with_message(&format!("extraction failed : {err}"));
+        }
+        None => (),
+    });
+
+    view! {
+        <Tool active name="extract" onclick />
+        <Show when=move || display.get()>
+            <ExtractCard display action current_path />
+        </Show>
+    }
+}
+
+#[component]
+fn ExtractCard(
+    display: RwSignal<bool>,
+    action: Action<Extraction, Result<u64, String>>,
+    current_path: RwSignal<PathBuf>,
+) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let to = RwSignal::new(
+        current_path
+            .get_untracked()
+            .to_str()
+            .unwrap_or_default()
+            .to_string(),
+    );
+    let naming = RwSignal::new(OutputNaming::default());
+
+    let _ = use_event_listener(use_window(), ev::keydown, move |ev| {
+        if ev.key() == "Escape" {
+            display.set(false);
+        }
+    });
+
+    let target = NodeRef::<Div>::new();
+    let _ = on_click_outside(target, move |_| {
+        display.set(false);
+    });
+
+    let on_naming = move |ev: Event| {
+        let i = ev
+            .target()
+            .unwrap()
+            .unchecked_into::<HtmlSelectElement>()
+            .selected_index()
+            .max(0) as usize;
+        naming.set(OutputNaming::ALL[i]);
+    };
+
+    let submit = move |_| {
+        action.dispatch_local(Extraction {
+            archives: store.select().read_untracked().as_paths(),
+            to: PathBuf::from(to.get_untracked().trim()),
+            naming: naming.get_untracked(),
+        });
+        store.select().write().clear();
+        display.set(false);
+    };
+
+    view! {
+        <div
+            class="fixed top-1/2 left-1/2 transform -translate-x-1/2 -translate-y-1/2 bg-white border-2 border-lime-500 rounded-lg p-5 grid grid-cols-2 gap-3 text-xl"
+            node_ref=target
+        >
+            <label>"into"</label>
+            <input class="border-2 border-black" placeholder="the root" bind:value=to />
+            <label>"if the name is taken"</label>
+            <select on:change=on_naming>
+                {OutputNaming::ALL
+                    .map(|x| view! { <option selected=x == naming.get_untracked()>{x.to_string()}</option> })}
+            </select>
+            <button class="col-span-2 border-2 border-black rounded-lg p-2 hover:bg-black hover:text-white" on:click=submit>
+                "extract"
+            </button>
+        </div>
+    }
+}
@@ -1,14 +1,45 @@
-use crate::{files_box::origin_with, nav_bar::Tool};
-use common::DISKS_PATH;
+use crate::{
+    files_box::{origin_with, path_as_query},
+    nav_bar::Tool,
+};
+use common::{
+    GlobalState, GlobalStateStoreFields, ProcessStatus, Store, DISKS_PATH, EJECT_PATH, MOUNT_PATH,
+    PARTITIONS_PATH, PROCESSES_PATH, UNMOUNT_PATH,
+};
 use leptos::{ev, html::Ul, prelude::*};
-use leptos_use::{on_click_outside, use_event_listener, use_window};
+use leptos_router::hooks::use_navigate;
+use leptos_use::{on_click_outside, use_event_listener, use_interval_fn, use_window};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Disk {
     total_space: u64,
     available_space: u64,
     name: String,
+    path: PathBuf,
+    file_system: String,
+    removable: bool,
+    read_only: bool,
+    inodes: Option<(u64, u64)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Disks {
+    disks: Vec<Disk>,
+    /// every filesystem counted once, however many shares live on it
+    total_space: u64,
+    available_space: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Partition {
+    name: String,
+    size: u64,
+    fstype: String,
+    label: Option<String>,
+    mountpoints: Vec<PathBuf>,
+    removable: bool,
 }
 
 #[component]
@@ -28,21 +59,69 @@ pub fn Info() -> impl IntoView {
     }
 }
 
-async fn get_disks() -> Result<Vec<Disk>, String> {
+async fn get_disks() -> Result<Disks, String> {
     let res = reqwest::Client::new()
         .get(origin_with(DISKS_PATH))
         .send()
         .await
         .map_err(|x| x.to_string())?
-        .json::<Vec<Disk>>()
+        .json::<Disks>()
         .await
         .map_err(|x| x.to_string())?;
     Ok(res)
 }
 
+async fn get_processes() -> Result<ProcessStatus, String> {
+    reqwest::Client::new()
+        .get(origin_with(PROCESSES_PATH))
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<ProcessStatus>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+async fn get_partitions() -> Result<Vec<Partition>, String> {
+    reqwest::Client::new()
+        .get(origin_with(PARTITIONS_PATH))
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<Vec<Partition>>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+/// mounts, unmounts or ejects the partition `name` depending on `endpoint`
+async fn partition_action(endpoint: &'static str, name: String) -> Result<(), String> {
+    let res = reqwest::Client::new()
+        .post(origin_with(endpoint))
+        .json(&name)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .text()
+        .await
+        .map_err(|x| x.to_string())?;
+    // success has no body, failures are the serialized server error
+    if res.is_empty() {
+        Ok(())
+    } else {
+        Err(res)
+    }
+}
+
 #[component]
 fn InfoCard(display: RwSignal<bool>) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
     let disks = LocalResource::new(get_disks);
+    let partitions = LocalResource::new(get_partitions);
+    let processes = LocalResource::new(get_processes);
+    let _ = use_interval_fn(move || processes.refetch(), 2000);
+    let action = Action::new_local(|(endpoint, name): &(&'static str, String)| {
+        partition_action(endpoint, name.clone())
+    });
 
     Effect::new(move || {
         if display.get() {
@@ -50,6 +129,20 @@ fn InfoCard(display: RwSignal<bool>) -> impl IntoView {
         }
     });
 
+    Effect::new(move || {
+        match action.value().get() {
+            Some(Err(err)) => {
+                let _ = window().alert_with_message(&err);
+            }
+            Some(Ok(())) => {
+                disks.refetch();
+                partitions.refetch();
+                store.units_refetch_tick().update(|x| *x = !*x);
+            }
+            None => (),
+        };
+    });
+
     let _ = use_event_listener(use_window(), ev::keydown, move |ev| {
         if ev.key() == "Escape" {
             display.set(false);
@@ -68,20 +161,120 @@ fn InfoCard(display: RwSignal<bool>) -> impl IntoView {
                 class="fixed top-1/2 left-1/2 transform -translate-x-1/2 -translate-y-1/2"
                 node_ref={target}
             >
+                {move || disks.get().and_then(Result::ok).map(|disks| view! { <TotalInfo disks/> })}
                 <For
-                    each=move ||disks.get().transpose().ok().flatten().unwrap_or(Vec::new())
-                    key=|x| x.name.clone()
+                    each=move || disks.get().and_then(Result::ok).map(|x| x.disks).unwrap_or_default()
+                    key=|x| x.path.clone()
                     let:disk
                 >
-                    <DiskInfo disk/>
+                    <DiskInfo disk display/>
+                </For>
+                <For
+                    each=move || partitions.get().transpose().ok().flatten().unwrap_or_default()
+                    key=|x| (x.name.clone(), x.mountpoints.clone())
+                    let:partition
+                >
+                    <PartitionInfo partition action />
                 </For>
+                {move || processes.get().and_then(Result::ok).map(|status| view! { <ProcessInfo status/> })}
             </ul>
         </Suspense>
     }
 }
 
 #[component]
-fn DiskInfo(disk: Disk) -> impl IntoView {
+fn PartitionInfo(
+    partition: Partition,
+    action: Action<(&'static str, String), Result<(), String>>,
+) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let title = partition.label.clone().unwrap_or(partition.name.clone());
+    let size = format!("{:.2}G", partition.size as f64 / 1024.0f64.powi(3));
+    let mounted = !partition.mountpoints.is_empty();
+    let state = if mounted { "MOUNTED" } else { "NOT MOUNTED" };
+
+    let button = move |endpoint: &'static str, label: &'static str| {
+        let name = partition.name.clone();
+        view! {
+            <button
+                class="border-2 border-black rounded-lg px-2 hover:bg-black hover:text-white"
+                disabled=move || action.pending().get()
+                on:click=move |_| {
+                    action.dispatch_local((endpoint, name.clone()));
+                }
+            >
+                {label}
+            </button>
+        }
+    };
+    let buttons = move || {
+        let admin = store.password().get() && !store.settings().read().read_only;
+        admin.then(|| {
+            view! {
+                {(!mounted).then(|| button(MOUNT_PATH, "mount"))}
+                {mounted.then(|| button(UNMOUNT_PATH, "unmount"))}
+                {partition.removable.then(|| button(EJECT_PATH, "eject"))}
+            }
+        })
+    };
+
+    view! {
+        <li>
+            <h3 class="text-3xl m-5">{title}</h3>
+            <div class="grid grid-cols-2 gap-5">
+                <span>{format!("{} {size}", partition.fstype)}</span>
+                <span>{state}</span>
+                <div class="flex gap-3">{buttons}</div>
+            </div>
+        </li>
+    }
+}
+
+#[component]
+fn ProcessInfo(status: ProcessStatus) -> impl IntoView {
+    let ProcessStatus {
+        running,
+        pending,
+        limit,
+    } = status;
+    view! {
+        <li>
+            <h3 class="text-3xl m-5">"processes"</h3>
+            <div class="grid grid-cols-2 gap-5">
+                <progress value={running.to_string()} max={limit.to_string()}/>
+                <span>{format!("RUNNING : {running}/{limit}")}</span>
+                <span>{format!("PENDING : {pending}")}</span>
+            </div>
+        </li>
+    }
+}
+
+/// the space of every listed filesystem together
+#[component]
+fn TotalInfo(disks: Disks) -> impl IntoView {
+    let used_space = disks.total_space - disks.available_space;
+    let free = format!(
+        "FREE : {:.2}G",
+        disks.available_space as f64 / 1024.0f64.powi(3)
+    );
+    let used = format!("USED : {:.2}G", used_space as f64 / 1024.0f64.powi(3));
+    view! {
+        <li>
+            <h3 class="text-3xl m-5">"total"</h3>
+            <div class="grid grid-cols-2 gap-5">
+                <progress value={used_space.to_string()} max={disks.total_space.to_string()}/>
+                <div class="grid grid-cols-2 gap-5">
+                    <span>{used}</span>
+                    <span>{free}</span>
+                </div>
+            </div>
+        </li>
+    }
+}
+
+#[component]
+fn DiskInfo(disk: Disk, display: RwSignal<bool>) -> impl IntoView {
+    let navigate = use_navigate();
     let used_space = disk.total_space - disk.available_space;
     let free = format!(
         "FREE : {:.2}G",
@@ -92,9 +285,24 @@ fn DiskInfo(disk: Disk) -> impl IntoView {
         "{:.2}%",
         (used_space as f64 / disk.total_space as f64) * 100.
     );
+    let mut flags = vec![disk.file_system];
+    flags.extend(disk.removable.then(|| "removable".to_string()));
+    flags.extend(disk.read_only.then(|| "read only".to_string()));
+    let inodes = disk.inodes.map(|(total, free)| {
+        format!(
+            "INODES : {:.2}%",
+            (total - free) as f64 / total as f64 * 100.
+        )
+    });
+    let open = move |_| {
+        navigate(&path_as_query(&disk.path), Default::default());
+        display.set(false);
+    };
     view! {
         <li>
-            <h3 class="text-3xl m-5">{disk.name}</h3>
+            <button class="text-3xl m-5 hover:underline" on:click=open>
+                {disk.name}
+            </button>
             <div class="grid grid-cols-2 gap-5">
                 <progress value={used_space.to_string()} max={disk.total_space.to_string()}/>
                 <span>{usage}</span>
@@ -102,6 +310,10 @@ fn DiskInfo(disk: Disk) -> impl IntoView {
                     <span>{used}</span>
                     <span>{free}</span>
                 </div>
+                <div class="grid grid-cols-2 gap-5">
+                    <span>{flags.join(", ")}</span>
+                    <span>{inodes}</span>
+                </div>
             </div>
         </li>
     }
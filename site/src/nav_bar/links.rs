@@ -0,0 +1,222 @@
+use crate::files_box::origin_with;
+use crate::nav_bar::Tool;
+use common::{
+    GlobalState, GlobalStateStoreFields, LinkInfo, NewLink, Store, LINKS_PATH, LINK_PREFIX,
+    LINK_RM_PATH,
+};
+use leptos::{ev, html::Div, prelude::*};
+use leptos_use::{on_click_outside, use_event_listener, use_window};
+use web_sys::{js_sys::Date, wasm_bindgen::JsCast, Event, HtmlSelectElement};
+
+/// how long a new link may be used, in seconds
+const EXPIRIES: [(&str, u64); 4] = [
+    ("an hour", 60 * 60),
+    ("a day", 24 * 60 * 60),
+    ("a week", 7 * 24 * 60 * 60),
+    ("a month", 30 * 24 * 60 * 60),
+];
+
+async fn links() -> Result<Vec<LinkInfo>, String> {
+    reqwest::Client::new()
+        .get(origin_with(LINKS_PATH))
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<Vec<LinkInfo>>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+async fn create(new: NewLink) -> Result<LinkInfo, String> {
+    reqwest::Client::new()
+        .post(origin_with(LINKS_PATH))
+        .json(&new)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .json::<LinkInfo>()
+        .await
+        .map_err(|x| x.to_string())
+}
+
+async fn revoke(token: String) -> Result<(), String> {
+    let _ = reqwest::Client::new()
+        .post(origin_with(LINK_RM_PATH))
+        .json(&token)
+        .send()
+        .await
+        .map_err(|x| x.to_string())?;
+    Ok(())
+}
+
+/// `in 3 h` like time left until `expires`, in unix seconds
+fn expires_text(expires: u64) -> String {
+    let left = expires.saturating_sub((Date::now() / 1000.) as u64);
+    match left {
+        0..60 => String::from("expired"),
+        60..3600 => format!("in {} min", left / 60),
+        3600..86400 => format!("in {} h", left / 3600),
+        _ => format!("in {} d", left / 86400),
+    }
+}
+
+#[component]
+pub fn Links() -> impl IntoView {
+    let display = RwSignal::new(false);
+    let onclick = move || {
+        display.set(true);
+    };
+
+    view! {
+        <Tool name="link" active=|| true onclick />
+        <Show when=move || display.get()>
+            <LinksCard display />
+        </Show>
+    }
+}
+
+#[component]
+fn LinksCard(display: RwSignal<bool>) -> impl IntoView {
+    let store = use_context::<Store<GlobalState>>().unwrap();
+    let list = LocalResource::new(links);
+    let create = Action::new_local(|x: &NewLink| create(x.clone()));
+    let revoke = Action::new_local(|x: &String| revoke(x.clone()));
+    let expires_in = RwSignal::new(EXPIRIES[1].1);
+    let password = RwSignal::new(String::new());
+    let max_downloads = RwSignal::new(String::new());
+
+    Effect::new(move || {
+        let _ = (create.version().get(), revoke.version().get());
+        list.refetch();
+    });
+
+    Effect::new(move || {
+        if let Some(Err(err)) = create.value().get() {
+            let _ = window().alert_with_message(&format!("no link made : {err}"));
+        }
+    });
+
+    let _ = use_event_listener(use_window(), ev::keydown, move |ev| {
+        if ev.key() == "Escape" {
+            display.set(false);
+        }
+    });
+
+    let target = NodeRef::<Div>::new();
+    let _ = on_click_outside(target, move |_| {
+        display.set(false);
+    });
+
+    // one file or directory is shared at a time
+    let selected = move || match store.select().read().units.as_slice() {
+        [unit] => Some(unit.clone()),
+        _ => None,
+    };
+
+    let on_expiry = move |ev: Event| {
+        let i = ev
+            .target()
+            .unwrap()
+            .unchecked_into::<HtmlSelectElement>()
+            .selected_index()
+            .max(0) as usize;
+        expires_in.set(EXPIRIES[i].1);
+    };
+
+    let submit = move |_| {
+        let Some(unit) = selected() else {
+            return;
+        };
+        create.dispatch_local(NewLink {
+            path: unit.path,
+            expires_in: expires_in.get_untracked(),
+            password: Some(password.get_untracked()).filter(|x| !x.is_empty()),
+            max_downloads: max_downloads.get_untracked().trim().parse().ok(),
+        });
+        password.write().clear();
+        max_downloads.write().clear();
+    };
+
+    let form = move || {
+        selected().map(|unit| {
+            view! {
+                <div class="grid grid-cols-2 gap-3 mb-5">
+                    <h2 class="col-span-2 font-bold truncate">{format!("share {}", unit.name())}</h2>
+                    <label>"for"</label>
+                    <select on:change=on_expiry>
+                        {EXPIRIES
+                            .map(|(name, x)| view! { <option selected=x == expires_in.get_untracked()>{name}</option> })}
+                    </select>
+                    <label>"password"</label>
+                    <input class="border-2 border-black" type="password" bind:value=password />
+                    <label>"downloads"</label>
+                    <input
+                        class="border-2 border-black"
+                        type="number"
+                        min="1"
+                        placeholder="any"
+                        bind:value=max_downloads
+                    />
+                    <button
+                        class="col-span-2 border-2 border-black rounded-lg p-2 hover:bg-black hover:text-white"
+                        on:click=submit
+                    >
+                        "make link"
+                    </button>
+                </div>
+            }
+        })
+    };
+
+    let rows = move || {
+        list.get().map(|links| match links {
+            Ok(links) if links.is_empty() => view! { <p>"no links"</p> }.into_any(),
+            Ok(links) => links
+                .into_iter()
+                .map(|link| view! { <LinkRow link revoke /> })
+                .collect_view()
+                .into_any(),
+            Err(err) => view! { <p>{err}</p> }.into_any(),
+        })
+    };
+
+    view! {
+        <div
+            class="fixed top-1/2 left-1/2 transform -translate-x-1/2 -translate-y-1/2 bg-white border-2 border-lime-500 rounded-lg p-5 text-xl max-w-2xl max-h-screen overflow-auto"
+            node_ref=target
+        >
+            {form}
+            <ul class="grid gap-3">{rows}</ul>
+        </div>
+    }
+}
+
+#[component]
+fn LinkRow(link: LinkInfo, revoke: Action<String, Result<(), String>>) -> impl IntoView {
+    let url = origin_with(&format!("{LINK_PREFIX}/{}", link.token));
+    let downloads = match link.max_downloads {
+        Some(max) => format!("{} of {max} downloads", link.downloads),
+        None => format!("{} downloads", link.downloads),
+    };
+    let mut notes = vec![expires_text(link.expires), downloads];
+    if link.password {
+        notes.push(String::from("password"));
+    }
+    let token = link.token.clone();
+
+    view! {
+        <li class="grid grid-cols-[1fr_auto] gap-x-3">
+            <span class="truncate">{link.path.to_str().unwrap_or_default().to_string()}</span>
+            <button
+                class="row-span-3 border-2 border-black rounded-lg px-2 hover:bg-black hover:text-white"
+                on:click=move |_| {
+                    revoke.dispatch_local(token.clone());
+                }
+            >
+                "revoke"
+            </button>
+            <input class="text-sm border border-gray-300" readonly value=url />
+            <span class="text-sm text-gray-500">{notes.join(", ")}</span>
+        </li>
+    }
+}
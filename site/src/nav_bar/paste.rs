@@ -1,6 +1,7 @@
 use crate::files_box::origin_with;
 use crate::nav_bar::LoadableTool;
-use common::{GlobalState, GlobalStateStoreFields, SelectedState, MV_PATH};
+use atoms::ActiveIcon;
+use common::{GlobalState, GlobalStateStoreFields, SelectedState, MV_PATH, VERIFY_PATH};
 use common::{Store, CP_PATH};
 use leptos::{ev, prelude::*};
 use leptos_use::{use_event_listener, use_window};
@@ -16,6 +17,20 @@ async fn cp(targets: Vec<PathBuf>, to: PathBuf) -> Result<(), String> {
     Ok(())
 }
 
+async fn verify(targets: Vec<PathBuf>, to: PathBuf) -> Result<(), String> {
+    let res = reqwest::Client::new()
+        .post(origin_with(VERIFY_PATH))
+        .json(&(targets, to))
+        .send()
+        .await
+        .map_err(|x| x.to_string())?
+        .text()
+        .await
+        .map_err(|x| x.to_string())?;
+    // the job id, or the serialized server error
+    res.parse::<u64>().map(|_| ()).map_err(|_| res)
+}
+
 async fn mv(targets: Vec<PathBuf>, to: PathBuf) -> Result<(), String> {
     reqwest::Client::new()
         .post(origin_with(MV_PATH))
@@ -31,10 +46,17 @@ pub fn Paste(current_path: RwSignal<PathBuf>) -> impl IntoView {
     let store: Store<GlobalState> = use_context().unwrap();
     let copy = Action::new_local({
         move |_: &()| {
-            cp(
-                store.select().read_untracked().as_paths(),
-                current_path.get_untracked(),
-            )
+            let targets = store.select().read_untracked().as_paths();
+            let to = current_path.get_untracked();
+            let verified = store.verify_copies().get_untracked();
+            async move {
+                cp(targets.clone(), to.clone()).await?;
+                if verified {
+                    verify(targets, to).await?;
+                    store.jobs_refetch_tick().update(|x| *x = !*x);
+                }
+                Ok::<_, String>(())
+            }
         }
     });
     let cut = Action::new_local(move |_: &()| {
@@ -48,6 +70,12 @@ pub fn Paste(current_path: RwSignal<PathBuf>) -> impl IntoView {
     let cut_finished = move || !cut.pending().get();
     let finished = move || cut_finished() && copy_finished();
 
+    Effect::new(move || {
+        if let Some(Err(err)) = copy.value().get() {
+            let _ = window().alert_with_message(&format!("copy failed : {err}"));
+        }
+    });
+
     Effect::new(move || {
         if finished() {
             store.select().write().clear();
@@ -78,6 +106,7 @@ pub fn Paste(current_path: RwSignal<PathBuf>) -> impl IntoView {
 
     view! {
         <Copy finished=copy_finished />
+        <VerifyCopies />
         <Cut finished=cut_finished />
         <LoadableTool active name="paste" onclick finished />
     }
@@ -110,6 +139,21 @@ where
     view! { <LoadableTool active name="copy" onclick finished /> }
 }
 
+/// a switch, bright while copies get verified once pasted
+#[component]
+fn VerifyCopies() -> impl IntoView {
+    let store: Store<GlobalState> = use_context().unwrap();
+    let on_click = move |_| {
+        store.verify_copies().update(|x| *x = !*x);
+    };
+
+    view! {
+        <button on:click=on_click>
+            <ActiveIcon name="verify" active=move || store.verify_copies().get() />
+        </button>
+    }
+}
+
 #[component]
 fn Cut<Finished>(finished: Finished) -> impl IntoView
 where
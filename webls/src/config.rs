@@ -0,0 +1,438 @@
+use serde::Deserialize;
+use server::{Quotas, Share};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io,
+    net::{IpAddr, Ipv4Addr},
+    path::{Component, Path, PathBuf},
+};
+use tracing_subscriber::EnvFilter;
+
+/// everything `webls` can be told, as read from its toml file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: IpAddr,
+    /// any free port when missing
+    pub port: Option<u16>,
+    /// the directory being served, drives get mounted inside it too
+    pub root: Option<PathBuf>,
+    /// directories served under their own names next to what the root holds
+    pub shares: Vec<Share>,
+    /// the built site, next to the executable when missing
+    pub site: Option<PathBuf>,
+    /// thumbnails, transcodes and probes
+    pub cache: Option<PathBuf>,
+    /// which uploads each user made, `~/.local/state/webls` when missing
+    pub state: Option<PathBuf>,
+    /// only browse and download
+    pub read_only: bool,
+    /// mount drives plugged into the host under the root
+    pub hotplug: bool,
+    pub auth: Auth,
+    pub limits: Limits,
+    pub mounts: partitions::Policy,
+    pub tls: Option<Tls>,
+    pub log: Log,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: None,
+            root: None,
+            shares: Vec::new(),
+            site: None,
+            cache: None,
+            state: None,
+            read_only: false,
+            hotplug: false,
+            auth: Auth::default(),
+            limits: Limits::default(),
+            mounts: partitions::Policy::default(),
+            tls: None,
+            log: Log::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Auth {
+    /// basic auth user names and the argon2 hashes of their passwords, as `--hash-password` prints
+    /// them, anyone may connect when empty
+    pub users: HashMap<String, String>,
+    /// users who may mount, unmount and eject partitions from the web ui
+    pub admins: HashSet<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// seconds a request may take
+    pub timeout: Option<u64>,
+    /// ffmpeg, ffprobe and mount processes running at once
+    pub processes: Option<usize>,
+    pub quotas: Quotas,
+}
+
+/// pem files when both are given, a self signed certificate otherwise
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tls {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    /// where the self signed certificate is kept, `tls` in the cache when missing
+    pub dir: Option<PathBuf>,
+    /// host names and addresses the self signed certificate is made for
+    pub names: Vec<String>,
+}
+
+impl Config {
+    /// what the server gets told, once validated
+    pub fn tls(&self) -> Option<server::Tls> {
+        let tls = self.tls.as_ref()?;
+        if let (Some(cert), Some(key)) = (&tls.cert, &tls.key) {
+            return Some(server::Tls::Files {
+                cert: cert.clone(),
+                key: key.clone(),
+            });
+        }
+        let mut names = tls.names.clone();
+        if names.is_empty() {
+            names.push(String::from("localhost"));
+            if !self.bind.is_unspecified() {
+                names.push(self.bind.to_string());
+            }
+        }
+        Some(server::Tls::SelfSigned {
+            dir: tls
+                .dir
+                .clone()
+                .or(self.cache.as_ref().map(|x| x.join("tls")))?,
+            names,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Log {
+    /// a `tracing` filter like `info` or `warn,tower_http=debug`
+    pub level: String,
+    /// standard error when missing
+    pub file: Option<PathBuf>,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    NoRoot,
+    /// what the directory is for and where it was looked for
+    NotADirectory(&'static str, PathBuf),
+    NoProcesses,
+    /// `log.level` and why `tracing` does not take it
+    LogLevel(String, String),
+    UserName(String),
+    /// the password of a user is not stored as an argon2 hash
+    PasswordHash(String),
+    /// share names must be unique single path components
+    ShareName(String),
+    /// a share lets in a user that can not log in
+    ShareUser(String, String),
+    /// an admin that can not log in
+    AdminUser(String),
+    QuotaDir(PathBuf),
+    /// a quota for a user that can not log in, so it would never apply
+    QuotaUser(String),
+    /// only one of `tls.cert` and `tls.key` was given
+    TlsPair,
+    /// a self signed certificate would be made anew every run
+    TlsDir,
+    NotAFile(&'static str, PathBuf),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(path, x) => write!(f, "can not read {} : {x}", path.display()),
+            Self::Parse(path, x) => write!(f, "invalid config {} : {x}", path.display()),
+            Self::NoRoot => write!(f, "no root to serve, set `root` or pass --root"),
+            Self::NotADirectory(what, path) => {
+                write!(f, "the {what} {} is not a directory", path.display())
+            }
+            Self::NoProcesses => write!(f, "`limits.processes` must be at least 1"),
+            Self::LogLevel(level, x) => write!(f, "invalid `log.level` {level:?} : {x}"),
+            Self::UserName(x) => write!(
+                f,
+                "invalid user name {x:?}, it can not be empty or hold ':'"
+            ),
+            Self::PasswordHash(x) => write!(
+                f,
+                "the password of {x:?} must be an argon2 hash, make one with --hash-password"
+            ),
+            Self::ShareName(x) => write!(
+                f,
+                "invalid share name {x:?}, names must be unique, not empty and hold no '/'"
+            ),
+            Self::ShareUser(share, user) => write!(
+                f,
+                "share {share:?} lets in {user:?} who is not one of `auth.users`"
+            ),
+            Self::AdminUser(x) => write!(f, "admin {x:?} is not one of `auth.users`"),
+            Self::QuotaDir(x) => write!(
+                f,
+                "quota directory {} must be relative to the root and stay inside it",
+                x.display()
+            ),
+            Self::QuotaUser(x) => {
+                write!(
+                    f,
+                    "`limits.quotas.users` names {x:?} who is not one of `auth.users`"
+                )
+            }
+            Self::TlsPair => write!(f, "`tls.cert` and `tls.key` go together"),
+            Self::TlsDir => write!(
+                f,
+                "set `tls.dir` or `cache` so the self signed certificate is kept between runs"
+            ),
+            Self::NotAFile(what, path) => write!(f, "the {what} {} is not a file", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(|x| Error::Read(path.to_path_buf(), x))?;
+        toml::from_str(&text).map_err(|x| Error::Parse(path.to_path_buf(), x))
+    }
+
+    /// catches what would otherwise only fail once the server runs
+    pub fn validate(&self) -> Result<(), Error> {
+        let root = self.root.as_ref().ok_or(Error::NoRoot)?;
+        if !root.is_dir() {
+            return Err(Error::NotADirectory("root", root.clone()));
+        }
+        if let Some(site) = self.site.as_ref().filter(|x| !x.is_dir()) {
+            return Err(Error::NotADirectory("site", site.clone()));
+        }
+        if self.limits.processes == Some(0) {
+            return Err(Error::NoProcesses);
+        }
+        if let Err(err) = EnvFilter::try_new(&self.log.level) {
+            return Err(Error::LogLevel(self.log.level.clone(), err.to_string()));
+        }
+        if let Some(user) = self
+            .auth
+            .users
+            .keys()
+            .find(|x| x.is_empty() || x.contains(':'))
+        {
+            return Err(Error::UserName(user.clone()));
+        }
+        if let Some((user, _)) = self
+            .auth
+            .users
+            .iter()
+            .find(|(_, hash)| !server::is_password_hash(hash))
+        {
+            return Err(Error::PasswordHash(user.clone()));
+        }
+        if let Some(admin) = self
+            .auth
+            .admins
+            .iter()
+            .find(|x| !self.auth.users.contains_key(*x))
+        {
+            return Err(Error::AdminUser(admin.clone()));
+        }
+        for (i, share) in self.shares.iter().enumerate() {
+            let taken = self.shares[..i].iter().any(|x| x.name == share.name);
+            let single = Path::new(&share.name)
+                .components()
+                .eq([Component::Normal(share.name.as_ref())]);
+            if taken || !single {
+                return Err(Error::ShareName(share.name.clone()));
+            }
+            if !share.path.is_dir() {
+                return Err(Error::NotADirectory("share", share.path.clone()));
+            }
+            if let Some(user) = share
+                .users
+                .iter()
+                .find(|x| !self.auth.users.contains_key(*x))
+            {
+                return Err(Error::ShareUser(share.name.clone(), user.clone()));
+            }
+        }
+        let outside = |x: &PathBuf| !x.components().all(|x| matches!(x, Component::Normal(_)));
+        if let Some(dir) = self.limits.quotas.dirs.keys().find(|x| outside(x)) {
+            return Err(Error::QuotaDir(dir.clone()));
+        }
+        if let Some(user) = self
+            .limits
+            .quotas
+            .users
+            .keys()
+            .find(|x| !self.auth.users.contains_key(*x))
+        {
+            return Err(Error::QuotaUser(user.clone()));
+        }
+        if let Some(tls) = &self.tls {
+            match (&tls.cert, &tls.key) {
+                (Some(cert), Some(key)) => {
+                    for (what, path) in [("tls cert", cert), ("tls key", key)] {
+                        if !path.is_file() {
+                            return Err(Error::NotAFile(what, path.clone()));
+                        }
+                    }
+                }
+                (None, None) if tls.dir.is_none() && self.cache.is_none() => {
+                    return Err(Error::TlsDir);
+                }
+                (None, None) => (),
+                _ => return Err(Error::TlsPair),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `secret`, hashed
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "$argon2id$v=19$m=19456,t=2,p=1$XooWnV4Diehg0GrJhQ40dw$HtYt24vD+0e21WyZSU2PlisqtzLY6N7TvTadCIjARcU";
+
+    #[test]
+    fn full_config() {
+        let config: Config = toml::from_str(
+            &r#"
+            bind = "127.0.0.1"
+            port = 8080
+            root = "/srv/media"
+
+            [auth]
+            users = { alice = "SECRET" }
+            admins = ["alice"]
+
+            [[shares]]
+            name = "docs"
+            path = "/home/alice/docs"
+            read_only = true
+            users = ["alice"]
+
+            [limits]
+            timeout = 600
+            processes = 2
+            quotas = { min_free = 1073741824, dirs = { uploads = 1000 } }
+
+            [mounts]
+            deny = [{ fs_type = "ntfs" }]
+
+            [log]
+            level = "warn,tower_http=debug"
+            "#
+            .replace("SECRET", SECRET),
+        )
+        .unwrap();
+
+        assert_eq!(config.bind, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(config.port, Some(8080));
+        assert_eq!(config.auth.users["alice"], SECRET);
+        assert!(config.auth.admins.contains("alice"));
+        assert_eq!(
+            config.shares,
+            [Share::new("docs".into(), "/home/alice/docs".into())
+                .read_only(true)
+                .users(vec!["alice".into()])]
+        );
+        assert_eq!(config.limits.processes, Some(2));
+        assert_eq!(config.limits.quotas.dirs[Path::new("uploads")], 1000);
+        assert_eq!(
+            config.mounts.deny,
+            [partitions::Rule::FsType("ntfs".into())]
+        );
+        assert_eq!(config.log.file, None);
+    }
+
+    #[test]
+    fn validation_errors() {
+        let root = std::env::temp_dir();
+        let valid = Config {
+            root: Some(root.clone()),
+            ..Config::default()
+        };
+        assert!(valid.validate().is_ok());
+
+        let mut config = valid.clone();
+        config.root = Some(root.join("webls-missing-root"));
+        assert!(matches!(
+            config.validate(),
+            Err(Error::NotADirectory("root", _))
+        ));
+
+        let mut config = valid.clone();
+        config.auth.users.insert("a:b".into(), SECRET.into());
+        assert!(matches!(config.validate(), Err(Error::UserName(_))));
+
+        let mut config = valid.clone();
+        config.auth.users.insert("alice".into(), "secret".into());
+        assert!(matches!(config.validate(), Err(Error::PasswordHash(_))));
+        config.auth.users.insert("alice".into(), SECRET.into());
+        assert!(config.validate().is_ok());
+
+        let mut config = valid.clone();
+        config.auth.admins.insert("bob".into());
+        assert!(matches!(config.validate(), Err(Error::AdminUser(_))));
+
+        let mut config = valid.clone();
+        config.shares = vec![
+            Share::new("media".into(), root.clone()),
+            Share::new("media".into(), root.clone()),
+        ];
+        assert!(matches!(config.validate(), Err(Error::ShareName(_))));
+
+        let mut config = valid.clone();
+        config.shares = vec![Share::new("media".into(), root.clone()).users(vec!["bob".into()])];
+        assert!(matches!(config.validate(), Err(Error::ShareUser(..))));
+
+        let mut config = valid.clone();
+        config.tls = Some(Tls {
+            cert: Some(root.clone()),
+            ..Tls::default()
+        });
+        assert!(matches!(config.validate(), Err(Error::TlsPair)));
+        config.tls = Some(Tls::default());
+        assert!(matches!(config.validate(), Err(Error::TlsDir)));
+
+        let mut config = valid.clone();
+        config.limits.quotas.dirs.insert("../escape".into(), 1);
+        assert!(matches!(config.validate(), Err(Error::QuotaDir(_))));
+
+        let mut config = valid.clone();
+        config.log.level = "warn,tower_http=loud".into();
+        assert!(matches!(config.validate(), Err(Error::LogLevel(..))));
+
+        let mut config = valid.clone();
+        config.limits.quotas.users.insert("bob".into(), 1);
+        assert!(matches!(config.validate(), Err(Error::QuotaUser(_))));
+
+        assert!(toml::from_str::<Config>("prot = 80").is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use clap::{Parser, builder::BoolishValueParser};
+use config::Config;
+use std::{
+    fs::OpenOptions, net::IpAddr, path::PathBuf, process::ExitCode, sync::Mutex, time::Duration,
+};
+use tracing_subscriber::EnvFilter;
+
+mod config;
+
+/// serves a directory to browsers on the network, without a gui
+///
+/// settings come from the config file, then the environment, then the command line
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// toml config file
+    #[arg(short, long, env = "WEBLS_CONFIG")]
+    config: Option<PathBuf>,
+    /// address to listen on
+    #[arg(long, env = "WEBLS_BIND")]
+    bind: Option<IpAddr>,
+    #[arg(short, long, env = "WEBLS_PORT")]
+    port: Option<u16>,
+    /// directory to serve
+    #[arg(short, long, env = "WEBLS_ROOT")]
+    root: Option<PathBuf>,
+    /// the built site
+    #[arg(long, env = "WEBLS_SITE")]
+    site: Option<PathBuf>,
+    #[arg(long, env = "WEBLS_CACHE")]
+    cache: Option<PathBuf>,
+    /// where what must outlive the cache is kept
+    #[arg(long, env = "WEBLS_STATE")]
+    state: Option<PathBuf>,
+    /// `--read-only=false` serves a config's read only root writable
+    #[arg(
+        long,
+        env = "WEBLS_READ_ONLY",
+        num_args = 0..=1,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    read_only: Option<bool>,
+    #[arg(long, env = "WEBLS_PROCESSES")]
+    processes: Option<usize>,
+    /// a `tracing` filter like `info` or `warn,tower_http=debug`
+    #[arg(long, env = "WEBLS_LOG")]
+    log: Option<String>,
+    /// pem certificate chain, https is served with it and `--tls-key`
+    #[arg(long, env = "WEBLS_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    #[arg(long, env = "WEBLS_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    #[arg(long, env = "WEBLS_LOG_FILE")]
+    log_file: Option<PathBuf>,
+    /// validate the configuration and exit
+    #[arg(long)]
+    check: bool,
+    /// print the hash to put in `auth.users` for the password read from standard input and exit
+    #[arg(long)]
+    hash_password: bool,
+}
+
+impl Cli {
+    fn config(self) -> Result<(Config, bool), config::Error> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        config.bind = self.bind.unwrap_or(config.bind);
+        config.port = self.port.or(config.port);
+        config.root = self.root.or(config.root);
+        config.site = self.site.or(config.site);
+        config.cache = self.cache.or(config.cache);
+        config.state = self.state.or(config.state);
+        config.read_only = self.read_only.unwrap_or(config.read_only);
+        config.limits.processes = self.processes.or(config.limits.processes);
+        config.log.level = self.log.unwrap_or(config.log.level);
+        config.log.file = self.log_file.or(config.log.file);
+        if let (Some(cert), Some(key)) = (self.tls_cert, self.tls_key) {
+            let tls = config.tls.get_or_insert_default();
+            (tls.cert, tls.key) = (Some(cert), Some(key));
+        }
+        Ok((config, self.check))
+    }
+}
+
+/// the site built next to the executable, where packaging puts it
+fn default_site() -> Option<PathBuf> {
+    let mut site = std::env::current_exe().ok()?.canonicalize().ok()?;
+    site.pop();
+    site.push("site");
+    Some(site)
+}
+
+fn init_log(log: &config::Log) -> Result<(), config::Error> {
+    let filter = EnvFilter::try_new(&log.level)
+        .map_err(|x| config::Error::LogLevel(log.level.clone(), x.to_string()))?;
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match &log.file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|x| config::Error::Read(path.clone(), x))?;
+            subscriber
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .init();
+        }
+        None => subscriber.with_writer(std::io::stderr).init(),
+    }
+    Ok(())
+}
+
+fn server(config: Config) -> server::Server {
+    let tls = config.tls();
+    let Config {
+        bind,
+        port,
+        root,
+        shares,
+        site,
+        cache,
+        state,
+        read_only,
+        hotplug,
+        auth,
+        limits,
+        mounts,
+        ..
+    } = config;
+    let site = site.or_else(default_site).unwrap_or_default();
+    let mut server = server::Server::new(site, root.unwrap_or_default())
+        .bind(bind)
+        .users(auth.users)
+        .admins(auth.admins)
+        .mount_policy(mounts)
+        .hotplug(hotplug)
+        .read_only(read_only)
+        .quotas(limits.quotas);
+    for share in shares {
+        server = server.share(share);
+    }
+    if let Some(port) = port {
+        server = server.port(port);
+    }
+    if let Some(cache) = cache {
+        server = server.cache(cache);
+    }
+    if let Some(state) = state {
+        server = server.state(state);
+    }
+    if let Some(timeout) = limits.timeout {
+        server = server.timeout(Duration::from_secs(timeout));
+    }
+    if let Some(processes) = limits.processes {
+        server = server.processes(processes);
+    }
+    if let Some(tls) = tls {
+        server = server.tls(tls);
+    }
+    server
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if cli.hash_password {
+        let mut password = String::new();
+        if let Err(err) = std::io::stdin().read_line(&mut password) {
+            eprintln!("can not read the password : {err}");
+            return ExitCode::FAILURE;
+        }
+        println!(
+            "{}",
+            server::hash_password(password.trim_end_matches(['\r', '\n']))
+        );
+        return ExitCode::SUCCESS;
+    }
+    let (config, check) = match cli.config() {
+        Ok(x) => x,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(err) = config.validate().and_then(|_| init_log(&config.log)) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+    if check {
+        println!("configuration is valid");
+        return ExitCode::SUCCESS;
+    }
+
+    if let Err(err) = server(config).serve().await {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}